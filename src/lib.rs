@@ -30,6 +30,8 @@ pub struct UnstakeReceipt {
     pub amount: Decimal,
     #[mutable]
     pub redemption_time: Instant,
+    pub id: Option<NonFungibleLocalId>,
+    pub queued_until: Option<Instant>,
 }
 
 // Staking ID structure, holding staked and locked amounts and date until which they are locked. Also stores the next period to claim rewards (updated after a user has claimed them).
@@ -39,19 +41,106 @@ pub struct Id {
     pub resources: HashMap<ResourceAddress, Resource>,
     #[mutable]
     pub next_period: i64,
+    #[mutable]
+    pub authorized_staker: Option<ResourceAddress>,
+    #[mutable]
+    pub label: String,
+    #[mutable]
+    pub outstanding_unstakes: u64,
+    #[mutable]
+    pub airdrops: HashMap<ResourceAddress, Decimal>,
+    #[mutable]
+    pub sweepable: bool,
+    #[mutable]
+    pub last_claim: Option<Instant>,
+    #[mutable]
+    pub total_claimed: Decimal,
 }
 
-// Lock structure, holding the information about locking options of a token.
-#[derive(ScryptoSbor)]
+// Lock structure, holding the information about locking options of a token. A duration of 0 disables locking the token.
+#[derive(ScryptoSbor, Clone)]
 pub struct Lock {
     pub payment: Decimal,
     pub duration: i64,
+    pub multiplier: Decimal,
 }
 
 #[derive(ScryptoSbor, Clone)]
 pub struct Resource {
     pub amount_staked: Decimal,
     pub locked_until: Option<Instant>,
+    pub amount_locked: Decimal,
+    pub last_stake_period: i64,
+    pub lock_multiplier: Decimal,
+    pub counted_locked: Decimal,
+    pub lock_end_period: i64,
+    pub dao_locked_until: Option<Instant>,
+    pub lock_payment: Decimal,
+    pub amount_unstaking: Decimal,
+    pub dao_counted: Decimal,
+    pub dao_lock_end_period: i64,
+}
+
+impl Resource {
+    // Amount of the staked tokens that is currently locked, tokens staked after locking are not locked and a lock no longer counts once expired.
+    pub fn locked_amount(&self) -> Decimal {
+        match self.locked_until {
+            Some(locked_until)
+                if !Clock::current_time_is_at_or_after(locked_until, TimePrecision::Minute) =>
+            {
+                self.amount_locked.min(self.amount_staked)
+            }
+            _ => dec!(0),
+        }
+    }
+
+    // Whether a DAO vote currently locks the staked tokens, a DAO lock cannot be force unlocked until it ends.
+    pub fn dao_locked(&self) -> bool {
+        match self.dao_locked_until {
+            Some(dao_locked_until) => {
+                !Clock::current_time_is_at_or_after(dao_locked_until, TimePrecision::Minute)
+            }
+            None => false,
+        }
+    }
+
+    // Amounts of staked tokens rewards are calculated over during a period, as the unlocked amount and the counted locked amount weighted by the multiplier of the lock they were locked with.
+    // Tokens only count as locked if the lock covers the period.
+    pub fn reward_weights(&self, period: i64) -> (Decimal, Decimal) {
+        let locked: Decimal = if period <= self.lock_end_period {
+            self.counted_locked
+        } else {
+            dec!(0)
+        };
+
+        (self.amount_staked - locked, locked * self.lock_multiplier)
+    }
+
+    // Amount the DAO locked tokens add to the stakable's DAO locked total from a period on, and the later changes to it, each applied after the period it is keyed by.
+    // DAO locked tokens already counted by the reward lock are left out until the reward lock ends, so no token is counted as locked twice.
+    pub fn dao_lock_counts(&self, period: i64) -> (Decimal, Vec<(i64, Decimal)>) {
+        if self.dao_lock_end_period < period || self.dao_counted == dec!(0) {
+            return (dec!(0), vec![]);
+        }
+
+        let overlap: Decimal = if self.lock_end_period >= period {
+            self.counted_locked.min(self.dao_counted)
+        } else {
+            dec!(0)
+        };
+
+        if overlap > dec!(0) && self.lock_end_period < self.dao_lock_end_period {
+            (
+                self.dao_counted - overlap,
+                vec![(self.lock_end_period, -overlap), (self.dao_lock_end_period, self.dao_counted)],
+            )
+        } else {
+            (
+                self.dao_counted - overlap,
+                vec![(self.dao_lock_end_period, self.dao_counted - overlap)],
+            )
+        }
+    }
 }
 
 // Stakable unit structure, used by the component to data about a stakable token.
@@ -62,51 +151,280 @@ pub struct StakableUnit {
     pub vault: Vault,
     pub reward_amount: Decimal,
     pub lock: Lock,
-    pub rewards: KeyValueStore<i64, Decimal>,
+    pub rewards: KeyValueStore<i64, (Decimal, Decimal)>,
+    pub reward_start_period: Option<i64>,
+    pub reward_end_period: Option<i64>,
+    pub last_reward_cut: Option<Instant>,
+    pub penalty_vault: Vault,
+    pub eligibility_delay: i64,
+    pub unstaking_amount: Decimal,
+    pub reward_budget: Option<Decimal>,
+    pub airdrop_schedule: KeyValueStore<i64, (ResourceAddress, Decimal)>,
+    pub airdrop_rates: KeyValueStore<i64, (ResourceAddress, Decimal)>,
+    pub emissions_paused: bool,
+    pub active: bool,
+    pub emission_curve: EmissionCurve,
+    pub compound_bonus: Decimal,
+    pub locked_amount: Decimal,
+    pub locked_weight: Decimal,
+    pub lock_expiries: KeyValueStore<i64, (Decimal, Decimal)>,
+    pub dao_locked_amount: Decimal,
+    pub dao_lock_expiries: KeyValueStore<i64, Decimal>,
+    pub require_lock_for_rewards: bool,
+    pub tiered_unstake_delay: Option<(Decimal, i64)>,
+}
+
+impl StakableUnit {
+    // Weighted amount of tokens the stakable's rewards are divided over, locked tokens weighted by their lock multiplier, only the locked tokens if rewards require a lock.
+    pub fn reward_base(&self) -> Decimal {
+        if self.require_lock_for_rewards {
+            self.locked_weight
+        } else {
+            self.amount_staked + self.locked_weight - self.locked_amount
+        }
+    }
+
+    // Reward emitted per period given the current total stake, following the stakable's emission curve.
+    pub fn emission(&self) -> Decimal {
+        match self.emission_curve {
+            EmissionCurve::Flat => self.reward_amount,
+            EmissionCurve::SquareRoot => {
+                self.reward_amount * self.amount_staked.checked_sqrt().unwrap_or(dec!(0))
+            }
+        }
+    }
+
+    // Reward emitted during a period at the current total stake, zero outside the reward window, while emissions are paused or when nothing earns rewards.
+    pub fn period_emission(&self, period: i64) -> Decimal {
+        let in_window = self.reward_start_period.map_or(true, |start| period >= start)
+            && self.reward_end_period.map_or(true, |end| period <= end);
+
+        if in_window && !self.emissions_paused && self.reward_base() > dec!(0) {
+            self.emission()
+        } else {
+            dec!(0)
+        }
+    }
+
+    // Reward emitted during a period after the global reward multiplier, capped to the stakable's reward budget if it has one.
+    pub fn budgeted_emission(&self, period: i64, global_reward_multiplier: Decimal) -> Decimal {
+        let emission: Decimal = self.period_emission(period) * global_reward_multiplier;
+
+        match self.reward_budget {
+            Some(reward_budget) => emission.min(reward_budget),
+            None => emission,
+        }
+    }
+}
+
+// Emission curve of a stakable, determining how its reward per period scales with its total stake.
+// Flat emits the reward amount regardless of the total stake, SquareRoot emits the reward amount times the square root of the total stake, so the reward per staked token diminishes as more is staked.
+#[derive(ScryptoSbor, Clone, Copy, PartialEq)]
+pub enum EmissionCurve {
+    Flat,
+    SquareRoot,
 }
 
 // Stake transfer receipt structure, minted when a user wants to transfer their staked tokens, redeemable by other users to add these tokens to their own staking ID.
+// While in transit, the tokens stay in the stakable's vault but are not counted as staked, so they earn no rewards and don't dilute the rewards of other stakers.
+// The sender has claimed all past periods before transferring and no longer holds the tokens, the receiver earns on them from the period in which the receipt is redeemed, so no period is paid twice.
 #[derive(ScryptoSbor, NonFungibleData)]
 pub struct StakeTransferReceipt {
     pub address: ResourceAddress,
     pub amount: Decimal,
 }
 
+// Reward vesting receipt structure, minted when rewards are claimed while vesting is enabled, redeemable linearly between the vesting start and end.
+#[derive(ScryptoSbor, NonFungibleData)]
+pub struct RewardVestingReceipt {
+    pub amount: Decimal,
+    pub vest_start: Instant,
+    pub vest_end: Instant,
+    #[mutable]
+    pub amount_claimed: Decimal,
+}
+
+// Event emitted when the configuration of a stakable is edited.
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct StakableEditedEvent {
+    pub address: ResourceAddress,
+    pub reward_amount: Decimal,
+    pub lock_payment: Decimal,
+    pub lock_duration: i64,
+}
+
+// Event emitted when the maximum claim delay is reduced, forfeiting the unclaimed rewards of periods that are no longer claimable.
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct MaxClaimDelayReducedEvent {
+    pub old_delay: i64,
+    pub new_delay: i64,
+    pub forfeited_rewards: Decimal,
+}
+
+// Setup information structure, returned to let front-ends verify a deployed component's configuration.
+#[derive(ScryptoSbor)]
+pub struct SetupInfo {
+    pub period_interval: i64,
+    pub max_unstaking_delay: i64,
+    pub max_lock_duration: i64,
+    pub dao_controlled: bool,
+    pub reward_resource: ResourceAddress,
+    pub lock_reward_resource: ResourceAddress,
+}
+
+// Maximum amount of entries a paginated read method returns in one call.
+const MAX_PAGE_SIZE: u64 = 100;
+
+// Maximum length in bytes of a staking ID's label.
+const MAX_LABEL_LENGTH: usize = 64;
+
+// Amount of decimal places reward rates are rounded down to if rounding down rewards is enabled.
+const REWARD_RATE_DECIMALS: i32 = 12;
+
 #[blueprint]
+#[events(StakableEditedEvent, MaxClaimDelayReducedEvent)]
 mod staking {
     enable_method_auth! {
+        roles {
+            treasury => updatable_by: [OWNER];
+        },
         methods {
             create_id => PUBLIC;
             stake => PUBLIC;
+            stake_new => PUBLIC;
+            stake_returning_id => PUBLIC;
+            stake_for => PUBLIC;
+            set_authorized_staker => PUBLIC;
+            set_label => PUBLIC;
+            set_sweepable => PUBLIC;
+            sweep_claim => restrict_to: [treasury];
+            collect_forfeited => restrict_to: [treasury];
             start_unstake => PUBLIC;
             finish_unstake => PUBLIC;
+            restake_receipt => PUBLIC;
+            emergency_unstake => PUBLIC;
+            transfer_receipt_to_unstake => PUBLIC;
             update_id => PUBLIC;
+            update_id_capped => PUBLIC;
+            update_id_and_swap => PUBLIC;
+            compound_rewards => PUBLIC;
             update_period => PUBLIC;
             lock_stake => PUBLIC;
+            sync_locks => PUBLIC;
+            force_unlock => PUBLIC;
+            claim_vested => PUBLIC;
+            receipt_counters => PUBLIC;
+            next_receipt_ids => PUBLIC;
+            receipt_info => PUBLIC;
+            unstake_redemption_time => PUBLIC;
+            unstake_receipts_info => PUBLIC;
+            claim_history => PUBLIC;
+            historical_staked => PUBLIC;
+            stakable_runway => PUBLIC;
+            ids_in_range => PUBLIC;
+            branding => PUBLIC;
+            reward_info => PUBLIC;
+            all_reward_rates => PUBLIC;
+            current_reward_rate => PUBLIC;
+            all_stakable_configs => PUBLIC;
+            unstaking_total => PUBLIC;
+            stakable_balance => PUBLIC;
+            is_stakable => PUBLIC;
+            staked_balances => PUBLIC;
+            id_next_period => PUBLIC;
+            preview_rewards => PUBLIC;
+            total_claimable => PUBLIC;
+            stake_share => PUBLIC;
+            reward_budget => PUBLIC;
+            has_active_locks => PUBLIC;
+            lock_participation => PUBLIC;
+            total_locked => PUBLIC;
+            preview_lock_reward => PUBLIC;
+            unstake_delay_info => PUBLIC;
+            seconds_to_next_period => PUBLIC;
+            get_lock_config => PUBLIC;
+            lock_terms => PUBLIC;
+            get_controller => PUBLIC;
+            setup_info => PUBLIC;
+            is_dao_controlled => PUBLIC;
+            total_distributed => PUBLIC;
+            health_check => PUBLIC;
+            assert_solvent => PUBLIC;
+            is_healthy => PUBLIC;
             set_lock => restrict_to: [OWNER];
             set_period_interval => restrict_to: [OWNER];
+            set_max_catchup_periods => restrict_to: [OWNER];
+            catch_up_periods => restrict_to: [OWNER];
             set_rewards => restrict_to: [OWNER];
+            configure_rewards => restrict_to: [OWNER];
+            set_keeper_reward => restrict_to: [OWNER];
+            set_global_reward_multiplier => restrict_to: [OWNER];
+            set_reward_window => restrict_to: [OWNER];
+            set_eligibility_delay => restrict_to: [OWNER];
+            set_emissions_paused => restrict_to: [OWNER];
+            set_stakable_active => restrict_to: [OWNER];
+            set_emission_curve => restrict_to: [OWNER];
+            set_compound_bonus => restrict_to: [OWNER];
+            set_require_lock_for_rewards => restrict_to: [OWNER];
+            set_tiered_unstake_delay => restrict_to: [OWNER];
+            set_reward_budget => restrict_to: [OWNER];
+            rebalance_rewards => restrict_to: [OWNER];
             set_max_claim_delay => restrict_to: [OWNER];
+            set_claim_cooldown => restrict_to: [OWNER];
+            set_max_lifetime_reward => restrict_to: [OWNER];
             fill_rewards => restrict_to: [OWNER];
+            schedule_airdrop => restrict_to: [OWNER];
+            remove_airdrop_tokens => restrict_to: [OWNER];
             remove_rewards => restrict_to: [OWNER];
+            retire_reward_token => restrict_to: [OWNER];
+            fill_lock_rewards => restrict_to: [OWNER];
+            remove_lock_rewards => restrict_to: [OWNER];
+            set_round_down_rewards => restrict_to: [OWNER];
+            reclaim_dust => restrict_to: [OWNER];
             add_stakable => restrict_to: [OWNER];
             edit_stakable => restrict_to: [OWNER];
             set_next_period_to_now => restrict_to: [OWNER];
             set_unstake_delay => restrict_to: [OWNER];
+            set_transfer_whitelist => restrict_to: [OWNER];
+            set_paused => restrict_to: [OWNER];
+            accelerate_unstake => restrict_to: [OWNER];
+            update_branding => restrict_to: [OWNER];
+            set_vesting_duration => restrict_to: [OWNER];
+            set_min_lock_duration => restrict_to: [OWNER];
+            set_unstake_penalty => restrict_to: [OWNER];
+            set_penalty_free_window => restrict_to: [OWNER];
+            set_emergency_penalty => restrict_to: [OWNER];
+            set_force_unlock_penalty => restrict_to: [OWNER];
+            collect_penalties => restrict_to: [OWNER];
+            set_max_outstanding_unstakes => restrict_to: [OWNER];
         }
     }
 
     struct Staking {
+        // address of the controller badge, owner of the staking component unless a different owner rule was given
+        controller: ResourceAddress,
         // interval in which rewards are distributed in days
         period_interval: i64,
         // time the next interval starts
         next_period: Instant,
+        // time the current period started, the previous period boundary even if the period interval changed since
+        period_start: Instant,
         // current period, starting at 0, incremented after each period_interval
         current_period: i64,
+        // maximum amount of periods rolled in one call, a component behind by more periods needs several calls to catch up
+        max_catchup_periods: i64,
+        // maximum amount of staking rewards a staking ID can claim over its lifetime, if none, there is no maximum
+        max_lifetime_reward: Option<Decimal>,
+        // minimum time between two claims of a staking ID in minutes
+        claim_cooldown: i64,
         // maximum amount of weeks rewards are stored for a user, after which they become unclaimable
         max_claim_delay: i64,
+        // last period whose unclaimed rewards have been released, periods up to it stay unclaimable even if the maximum claim delay is raised
+        released_through: i64,
         // maximum unstaking delay the admin can set
         max_unstaking_delay: i64,
+        // maximum lock duration the admin can set in days
+        max_lock_duration: i64,
         // resource manager of the stake transfer receipts
         stake_transfer_receipt_manager: ResourceManager,
         // counter for the stake transfer receipts
@@ -115,36 +433,97 @@ mod staking {
         unstake_receipt_manager: ResourceManager,
         // counter for the unstake receipts
         unstake_receipt_counter: u64,
+        // minimum duration of a stakable's lock in days, so locks can't pay out without any commitment, stakables that are not lockable have a lock duration of 0
+        min_lock_duration: i64,
         // delay after which unstaked tokens can be redeemed in days
         unstake_delay: i64,
+        // fraction of unstaked tokens charged as a penalty when unstaking
+        unstake_penalty: Decimal,
+        // period after a stakable's reward is cut during which unstaking it is penalty-free in days
+        penalty_free_window: i64,
+        // fraction of tokens charged as a penalty when unstaking immediately through an emergency unstake, if none, emergency unstaking is disabled
+        emergency_penalty: Option<Decimal>,
+        // fraction of tokens charged as a penalty when unlocking them early through a force unlock, if none, force unlocking is disabled
+        force_unlock_penalty: Option<Decimal>,
+        // maximum amount of unstake receipts a staking ID can have outstanding at once, if 0, there is no maximum
+        max_outstanding_unstakes: u64,
         // resource manager of the staking IDs
         id_manager: ResourceManager,
         // counter for the staking IDs
         id_counter: u64,
         // vault that stores staking rewards
         reward_vault: FungibleVault,
+        // vault that stores rewards for locking staked tokens, kept apart from the staking rewards and possibly of a different resource
+        lock_reward_vault: FungibleVault,
+        // resource manager of the reward vesting receipts
+        vesting_receipt_manager: ResourceManager,
+        // counter for the reward vesting receipts
+        vesting_receipt_counter: u64,
+        // vault that stores claimed rewards that are still vesting
+        vesting_vault: FungibleVault,
+        // duration over which claimed rewards vest in days, if 0, claimed rewards are paid out directly
+        vesting_duration: i64,
+        // total staking rewards paid out to stakers, including lock rewards if they are paid in the reward token
+        total_rewards_distributed: Decimal,
+        // rewards recorded for past periods that have not been claimed yet
+        reserved_rewards: Decimal,
+        // multiplier applied to the rewards of all stakables, e.g. to boost rewards during a campaign
+        global_reward_multiplier: Decimal,
+        // reward paid from the reward vault to whoever rolls a period by calling update_period
+        keeper_reward: Decimal,
+        // whether reward rates are rounded down, sweeping the rounding dust into the dust vault so rewards are never over-distributed
+        round_down_rewards: bool,
+        // vault that stores the rewards left over by rounding down reward rates
+        dust_vault: FungibleVault,
+        // rewards recorded per period that have not been claimed yet
+        unclaimed_period_rewards: KeyValueStore<i64, Decimal>,
+        // vault that stores rewards forfeited by reducing the maximum claim delay, collectable by the treasury
+        forfeited_vault: FungibleVault,
+        // claimed reward per staking ID per period, grows by at most max_claim_delay entries per claim
+        claimed_rewards: KeyValueStore<(NonFungibleLocalId, i64), Decimal>,
+        // total staked amount per stakable per recorded period, grows by one entry per stakable per period
+        staked_history: KeyValueStore<(ResourceAddress, i64), Decimal>,
+        // vaults holding the tokens of scheduled airdrops, per airdropped token
+        airdrop_vaults: KeyValueStore<ResourceAddress, Vault>,
+        // airdropped tokens allocated to stakers but not yet paid out, per airdropped token, the rest of an airdrop vault can be removed
+        allocated_airdrops: KeyValueStore<ResourceAddress, Decimal>,
         // keyvaluestore, holding stakable units and their data
         stakes: HashMap<ResourceAddress, StakableUnit>,
         // whether a DAO is controlling the staking
         // If a centralized entity controls the controller badge, using the set_lock method, they could lock the someone's tokens by telling the system someone is voting.
         // To prevent this, this functionality only enabled if dao_controlled is set to true.
         dao_controlled: bool,
+        // name of the project, used in the metadata of the resources of the component
+        name: String,
+        // symbol of the project, used in the metadata of the resources of the component
+        symbol: String,
+        // badge required to redeem stake transfer receipts, if none, anyone can redeem them
+        transfer_whitelist: Option<ResourceAddress>,
+        // whether staking is paused, while paused no stake can be added to any staking ID, but unstakes can still be finished
+        paused: bool,
     }
 
     impl Staking {
         // this function instantiates the staking component
         //
         // ## INPUT
-        // - `controller`: the address of the controller badge, which will be the owner of the staking component
+        // - `controller`: the address of the controller badge, which will be the owner of the staking component and its resources if no owner rule is given
         // - `rewards`: the initial rewards the staking component holds
         // - `period_interval`: the interval in which rewards are distributed in days
         // - `name`: the name of your project
         // - `symbol`: the symbol of your project
+        // - `dao_controlled`: whether a DAO is controlling the staking, enabling the set_lock method
+        // - `max_unstaking_delay`: the maximum unstaking delay the admin can set in days
+        // - `max_lock_duration`: the maximum lock duration the admin can set in days
+        // - `owner_rule`: an optional owner rule for the component and its resources, e.g. requiring any of several badges, if none, the controller badge is required, it also governs the metadata, minting and data update roles of the resources
+        // - `lock_reward_resource`: an optional fungible resource lock rewards are paid in, if none, lock rewards are paid in the reward token
+        // - `transferable_ids`: whether staking IDs can be withdrawn from an account, letting staking positions be sold, rewards are always claimed by the current holder
         //
         // ## OUTPUT
         // - the staking component
         //
         // ## LOGIC
+        // - the maximum lock duration is checked to be at least the initial minimum lock duration of 1 day
         // - all resource managers are created
         // - the rewards are put into the reward vault and other values are set appropriately
         // - the staking component is instantiated
@@ -156,14 +535,33 @@ mod staking {
             symbol: String,
             dao_controlled: bool,
             max_unstaking_delay: i64,
+            max_lock_duration: i64,
+            owner_rule: Option<AccessRule>,
+            lock_reward_resource: Option<ResourceAddress>,
+            transferable_ids: bool,
         ) -> Global<Staking> {
             let (address_reservation, component_address) =
                 Runtime::allocate_component_address(Staking::blueprint_id());
 
+            assert!(
+                max_lock_duration >= 1,
+                "Maximum lock duration cannot be shorter than the minimum lock duration."
+            );
+
+            let owner_rule: AccessRule = owner_rule.unwrap_or(rule!(require(controller)));
+            let component_or_owner: AccessRule =
+                Self::component_or_owner_rule(component_address, &owner_rule);
+
             let id_manager = ResourceBuilder::new_integer_non_fungible::<Id>(OwnerRole::Fixed(
-                rule!(require(controller)),
+                owner_rule.clone(),
             ))
             .metadata(metadata!(
+                roles {
+                    metadata_setter => component_or_owner.clone();
+                    metadata_setter_updater => rule!(deny_all);
+                    metadata_locker => rule!(deny_all);
+                    metadata_locker_updater => rule!(deny_all);
+                },
                 init {
                     "name" => format!("{} Staking ID", name), updatable;
                     "symbol" => format!("id{}", symbol), updatable;
@@ -171,11 +569,7 @@ mod staking {
                 }
             ))
             .mint_roles(mint_roles!(
-                minter => rule!(require(global_caller(component_address))
-                || require_amount(
-                    dec!("0.75"),
-                    controller
-                ));
+                minter => component_or_owner.clone();
                 minter_updater => rule!(deny_all);
             ))
             .burn_roles(burn_roles!(
@@ -183,23 +577,25 @@ mod staking {
                 burner_updater => rule!(deny_all);
             ))
             .withdraw_roles(withdraw_roles!(
-                withdrawer => rule!(deny_all);
+                withdrawer => if transferable_ids { rule!(allow_all) } else { rule!(deny_all) };
                 withdrawer_updater => rule!(deny_all);
             ))
             .non_fungible_data_update_roles(non_fungible_data_update_roles!(
-                non_fungible_data_updater => rule!(require(global_caller(component_address))
-                || require_amount(
-                    dec!("0.75"),
-                    controller
-                ));
+                non_fungible_data_updater => component_or_owner.clone();
                 non_fungible_data_updater_updater => rule!(deny_all);
             ))
             .create_with_no_initial_supply();
 
-            let stake_transfer_receipt_manager = ResourceBuilder::new_integer_non_fungible::<UnstakeReceipt>(
-                OwnerRole::Fixed(rule!(require(controller))),
+            let stake_transfer_receipt_manager = ResourceBuilder::new_integer_non_fungible::<StakeTransferReceipt>(
+                OwnerRole::Fixed(owner_rule.clone()),
             )
             .metadata(metadata!(
+                roles {
+                    metadata_setter => component_or_owner.clone();
+                    metadata_setter_updater => rule!(deny_all);
+                    metadata_locker => rule!(deny_all);
+                    metadata_locker_updater => rule!(deny_all);
+                },
                 init {
                     "name" => format!("{} Stake Transfer Receipt", name), updatable;
                     "symbol" => format!("staketr{}", symbol), updatable;
@@ -218,9 +614,15 @@ mod staking {
 
             let unstake_receipt_manager =
                 ResourceBuilder::new_integer_non_fungible::<UnstakeReceipt>(OwnerRole::Fixed(
-                    rule!(require(controller)),
+                    owner_rule.clone(),
                 ))
                 .metadata(metadata!(
+                    roles {
+                        metadata_setter => component_or_owner.clone();
+                        metadata_setter_updater => rule!(deny_all);
+                        metadata_locker => rule!(deny_all);
+                        metadata_locker_updater => rule!(deny_all);
+                    },
                     init {
                         "name" => format!("{} Unstake Receipt", name), updatable;
                         "symbol" => format!("unstake{}", symbol), updatable;
@@ -241,74 +643,130 @@ mod staking {
                 ))
                 .create_with_no_initial_supply();
 
+            let vesting_receipt_manager =
+                ResourceBuilder::new_integer_non_fungible::<RewardVestingReceipt>(OwnerRole::Fixed(
+                    owner_rule.clone(),
+                ))
+                .metadata(metadata!(
+                    roles {
+                        metadata_setter => component_or_owner.clone();
+                        metadata_setter_updater => rule!(deny_all);
+                        metadata_locker => rule!(deny_all);
+                        metadata_locker_updater => rule!(deny_all);
+                    },
+                    init {
+                        "name" => format!("{} Reward Vesting Receipt", name), updatable;
+                        "symbol" => format!("vest{}", symbol), updatable;
+                        "description" => format!("A receipt for vesting staking rewards in the {} ecosystem.", name), updatable;
+                    }
+                ))
+                .mint_roles(mint_roles!(
+                    minter => rule!(require(global_caller(component_address)));
+                    minter_updater => rule!(deny_all);
+                ))
+                .burn_roles(burn_roles!(
+                    burner => rule!(require(global_caller(component_address)));
+                    burner_updater => rule!(deny_all);
+                ))
+                .non_fungible_data_update_roles(non_fungible_data_update_roles!(
+                    non_fungible_data_updater => rule!(require(global_caller(component_address)));
+                    non_fungible_data_updater_updater => rule!(deny_all);
+                ))
+                .create_with_no_initial_supply();
+
             Self {
+                controller,
                 next_period: Clock::current_time_rounded_to_minutes()
                     .add_days(period_interval)
                     .unwrap(),
+                period_start: Clock::current_time_rounded_to_minutes(),
                 period_interval,
                 current_period: 0,
+                max_catchup_periods: 50,
                 max_claim_delay: 5,
+                released_through: -1,
+                claim_cooldown: 0,
+                max_lifetime_reward: None,
                 max_unstaking_delay,
+                max_lock_duration,
                 unstake_delay: 7,
+                min_lock_duration: 1,
+                unstake_penalty: dec!(0),
+                penalty_free_window: 7,
+                emergency_penalty: None,
+                force_unlock_penalty: None,
+                max_outstanding_unstakes: 0,
                 id_manager,
                 stake_transfer_receipt_manager,
                 stake_transfer_receipt_counter: 0,
                 unstake_receipt_manager,
                 unstake_receipt_counter: 0,
                 id_counter: 0,
+                vesting_receipt_manager,
+                vesting_receipt_counter: 0,
+                vesting_vault: FungibleVault::new(rewards.resource_address()),
+                lock_reward_vault: FungibleVault::new(
+                    lock_reward_resource.unwrap_or(rewards.resource_address()),
+                ),
+                vesting_duration: 0,
                 reward_vault: FungibleVault::with_bucket(rewards.as_fungible()),
+                total_rewards_distributed: dec!(0),
+                reserved_rewards: dec!(0),
+                global_reward_multiplier: dec!(1),
+                keeper_reward: dec!(0),
+                round_down_rewards: false,
+                dust_vault: FungibleVault::new(rewards.resource_address()),
+                unclaimed_period_rewards: KeyValueStore::new(),
+                forfeited_vault: FungibleVault::new(rewards.resource_address()),
+                claimed_rewards: KeyValueStore::new(),
+                staked_history: KeyValueStore::new(),
+                airdrop_vaults: KeyValueStore::new(),
+                allocated_airdrops: KeyValueStore::new(),
                 stakes: HashMap::new(),
                 dao_controlled,
+                name,
+                symbol,
+                transfer_whitelist: None,
+                paused: false,
             }
             .instantiate()
-            .prepare_to_globalize(OwnerRole::Fixed(rule!(require(controller))))
+            .prepare_to_globalize(OwnerRole::Fixed(owner_rule.clone()))
+            .roles(roles!(
+                treasury => owner_rule;
+            ))
             .with_address(address_reservation)
             .globalize()
         }
 
-        // this method updates the component's period and saves the rewards accompanying the period
+        // this method updates the component's period and saves the rewards accompanying the period, paying a keeper reward to the caller if a period was rolled
         //
         // ## INPUT
         // - none
         //
         // ## OUTPUT
-        // - none
+        // - the keeper reward, if a period was rolled and a keeper reward is set
         // 
         // ## LOGIC
-        // - the method calculates the number of extra periods that have passed since the last update, because the method might not be called exactly at the end of a period
-        // - if a period has passed, for each stakable token the rewards are calculated and recorded, reward calculation is relatively simple:
-        //    - every stakable has a total amount of reward per period
-        //    - total reward amount is divided by the total amount staked to get the reward per staked token
-        // - the current period is incremented and the next period is set
-        pub fn update_period(&mut self) {
-            let extra_periods_dec: Decimal = ((Clock::current_time_rounded_to_minutes()
-                .seconds_since_unix_epoch
-                - self.next_period.seconds_since_unix_epoch)
-                / (Decimal::from(self.period_interval) * dec!(86400)))
-            .checked_floor()
-            .unwrap();
-
-            let extra_periods: i64 = i64::try_from(extra_periods_dec.0 / Decimal::ONE.0).unwrap();
-
-            if Clock::current_time_is_at_or_after(self.next_period, TimePrecision::Minute) {
-                for (_address, stakable_unit) in self.stakes.iter_mut() {
-                    if stakable_unit.amount_staked > dec!(0) {
-                        stakable_unit.rewards.insert(
-                            self.current_period,
-                            stakable_unit.reward_amount / stakable_unit.amount_staked,
-                        );
-                    } else {
-                        stakable_unit.rewards.insert(self.current_period, dec!(0));
-                    }
-                }
+        // - the method rolls the period if one has passed (see `roll_period`)
+        // - if a period was rolled and a keeper reward is set, the keeper reward is taken from the reward vault and returned
+        // - the keeper reward is capped to the unreserved rewards, so rewards owed to stakers are never paid out, and nothing is paid if none are unreserved
+        // - the paid keeper reward is added to the total rewards distributed
+        // - as a period can only be rolled once, only one keeper reward is paid per period
+        pub fn update_period(&mut self) -> Option<FungibleBucket> {
+            if !self.roll_period() || self.keeper_reward <= dec!(0) {
+                return None;
+            }
 
-                self.current_period += 1;
-                self.next_period = self
-                    .next_period
-                    .add_days((1 + extra_periods) * self.period_interval)
-                    .unwrap();
+            let unreserved: Decimal = (self.reward_vault.amount() - self.reserved_rewards).max(dec!(0));
+            let keeper_reward: Decimal = self.keeper_reward.min(unreserved);
+            if keeper_reward == dec!(0) {
+                return None;
             }
+
+            self.total_rewards_distributed += keeper_reward;
+            Some(self.reward_vault.take(keeper_reward))
         }
+
         // This method requests an unstake of staked tokens
         //
         // ## INPUT
@@ -316,31 +774,66 @@ mod staking {
         // - `address`: the address of the stakable token
         // - `amount`: the amount of tokens to unstake
         // - `stake_transfer`: whether to transfer the staked tokens to another user
+        // - `auto_claim`: whether to claim the staking ID's unclaimed rewards before unstaking
+        // - `allow_locked_queue`: whether to also unstake locked tokens, redeemable once both the unstake delay has passed and the lock has expired
         //
         // ## OUTPUT
         // - the unstake receipt / transfer receipt
+        // - if auto claiming, the claimed rewards, if there were rewards to claim, followed by all airdrops claimed by the staking ID
         //
         // ## LOGIC
+        // - the method checks the requested amount is positive
         // - the method checks the staking ID
+        // - if auto claiming, the method updates the component period and claims the staking ID's rewards before its stake is reduced, paying out its airdrops along with them
+        // - if transferring, the method checks the staking ID's rewards have been claimed, so the sender is paid for every period before the transfer
         // - the method checks the staked amount
-        // - the method checks if the staked tokens are locked (then unstaking is not possible)
-        // - if not, tokens are removed from staking ID stake
+        // - the method checks if the requested tokens are locked (then unstaking is not possible, unless queueing locked tokens for an unstake)
+        // - if all tokens are requested while some are locked, only the unlocked tokens are unstaked, unless queueing locked tokens for an unstake
+        // - tokens are removed from staking ID stake and from the stakable's total stake, so they stop earning rewards from the current period on
         // - if the user wants to transfer the tokens, a transfer receipt is minted
-        // - if the user wants to unstake the tokens, an unstake receipt is minted
+        // - if the user wants to unstake the tokens, the method checks the staking ID doesn't exceed the maximum of outstanding unstake receipts
+        // - if the user wants to unstake the tokens, the unstake penalty is charged (waived shortly after a reward cut) and an unstake receipt is minted
+        // - the unstake receipt is redeemable after the unstake delay, or the stakable's longer tiered delay if the staking ID's unstaking amount, including unfinished earlier unstakes, exceeds its threshold
+        // - if locked tokens are unstaked, they are removed from the staking ID's locked amount and the unstake receipt is not redeemable before the lock expires, which the receipt records
         pub fn start_unstake(
             &mut self,
             id_proof: NonFungibleProof,
             address: ResourceAddress,
             amount: Decimal,
             stake_transfer: bool,
-        ) -> Bucket {
+            auto_claim: bool,
+            allow_locked_queue: bool,
+        ) -> (Bucket, Vec<Bucket>) {
+            assert!(amount > dec!(0), "Unstake amount must be positive.");
+
             let id_proof =
                 id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
 
             let id = id_proof.non_fungible::<Id>().local_id().clone();
+
+            let claimed_rewards: Vec<Bucket> = if auto_claim {
+                self.roll_period();
+                let mut payout: Vec<Bucket> = self
+                    .claim_rewards(&id, None)
+                    .map(|staking_reward| self.pay_rewards(staking_reward))
+                    .into_iter()
+                    .collect();
+                payout.extend(self.pay_airdrops(&id));
+                payout
+            } else {
+                vec![]
+            };
+
             let id_data: Id = self.id_manager.get_non_fungible_data(&id);
 
-            let mut unstake_amount: Decimal = amount;
+            if stake_transfer {
+                assert!(
+                    id_data.next_period > self.current_period,
+                    "Please claim unclaimed rewards on your ID before transferring stake."
+                );
+            }
+
+            let unstake_amount: Decimal;
             let mut resource_map = id_data.resources.clone();
             let mut resource = resource_map
                 .get(&address)
@@ -352,51 +845,102 @@ mod staking {
                 "No stake available to unstake."
             );
 
-            if let Some(locked_until) = resource.locked_until {
+            let unlocked_amount: Decimal = resource.amount_staked - resource.locked_amount();
+            let unstakable_amount: Decimal = if allow_locked_queue && !stake_transfer {
+                resource.amount_staked
+            } else {
+                unlocked_amount
+            };
+
+            assert!(
+                unstakable_amount > dec!(0),
+                "You cannot unstake tokens currently participating in a vote."
+            );
+
+            if amount >= resource.amount_staked {
+                unstake_amount = unstakable_amount;
+            } else {
                 assert!(
-                    Clock::current_time_is_at_or_after(locked_until, TimePrecision::Minute),
+                    amount <= unstakable_amount,
                     "You cannot unstake tokens currently participating in a vote."
                 );
+                unstake_amount = amount;
             }
 
-            if amount >= resource.amount_staked {
-                unstake_amount = resource.amount_staked;
-                resource.amount_staked = dec!(0);
+            let queued_until: Option<Instant> = if unstake_amount > unlocked_amount {
+                resource.amount_locked -= unstake_amount - unlocked_amount;
+                resource.locked_until
             } else {
-                resource.amount_staked -= amount;
-            }
-
-            self.stakes.get_mut(&address).unwrap().amount_staked -= resource.amount_staked;
+                None
+            };
 
-            resource_map.insert(address, resource);
+            resource.amount_staked -= unstake_amount;
 
-            self.id_manager
-                .update_non_fungible_data(&id, "resources", resource_map);
+            self.stakes.get_mut(&address).unwrap().amount_staked -= unstake_amount;
+            self.recount_lock(address, &mut resource);
 
-            if stake_transfer {
+            let receipt: Bucket = if stake_transfer {
                 let stake_transfer_receipt = StakeTransferReceipt {
                     address,
                     amount: unstake_amount,
                 };
-                self.stake_transfer_receipt_counter += 1;
+                self.stake_transfer_receipt_counter = self
+                    .stake_transfer_receipt_counter
+                    .checked_add(1)
+                    .expect("Stake transfer receipt counter overflow.");
                 self.stake_transfer_receipt_manager.mint_non_fungible(
                     &NonFungibleLocalId::integer(self.stake_transfer_receipt_counter),
                     stake_transfer_receipt,
                 )
             } else {
+                assert!(
+                    self.max_outstanding_unstakes == 0
+                        || id_data.outstanding_unstakes < self.max_outstanding_unstakes,
+                    "Too many outstanding unstake receipts, finish an unstake first."
+                );
+                self.id_manager.update_non_fungible_data(
+                    &id,
+                    "outstanding_unstakes",
+                    id_data.outstanding_unstakes + 1,
+                );
+
+                let penalty: Decimal =
+                    self.collect_penalty(address, unstake_amount * self.unstake_penalty_for(address));
+                self.stakes.get_mut(&address).unwrap().unstaking_amount += unstake_amount - penalty;
+                resource.amount_unstaking += unstake_amount - penalty;
+
+                let mut redemption_time: Instant = Clock::current_time_rounded_to_minutes()
+                    .add_days(self.unstake_delay_for(address, Some(resource.amount_unstaking)))
+                    .unwrap();
+                if let Some(queued_until) = queued_until {
+                    if queued_until.seconds_since_unix_epoch > redemption_time.seconds_since_unix_epoch {
+                        redemption_time = queued_until;
+                    }
+                }
+
                 let unstake_receipt = UnstakeReceipt {
                     address,
-                    amount: unstake_amount,
-                    redemption_time: Clock::current_time_rounded_to_minutes()
-                        .add_days(self.unstake_delay)
-                        .unwrap(),
+                    amount: unstake_amount - penalty,
+                    redemption_time,
+                    id: Some(id.clone()),
+                    queued_until,
                 };
-                self.unstake_receipt_counter += 1;
+                self.unstake_receipt_counter = self
+                    .unstake_receipt_counter
+                    .checked_add(1)
+                    .expect("Unstake receipt counter overflow.");
                 self.unstake_receipt_manager.mint_non_fungible(
                     &NonFungibleLocalId::integer(self.unstake_receipt_counter),
                     unstake_receipt,
                 )
-            }
+            };
+
+            resource_map.insert(address, resource);
+
+            self.id_manager
+                .update_non_fungible_data(&id, "resources", resource_map);
+
+            (receipt, claimed_rewards)
         }
 
         // This method finishes an unstake, redeeming the unstaked tokens
@@ -411,9 +955,13 @@ mod staking {
         // - the method checks the receipt
         // - the method checks the redemption time
         // - the method burns the receipt
-        // - the method returns the unstaked tokens
+        // - the method frees up an outstanding unstake slot on the staking ID that requested the unstake
+        // - the method returns the unstaked tokens, also while staking is paused, as it only completes an exit and never adds stake
         pub fn finish_unstake(&mut self, receipt: Bucket) -> Bucket {
-            assert!(receipt.resource_address() == self.unstake_receipt_manager.address());
+            assert!(
+                receipt.resource_address() == self.unstake_receipt_manager.address(),
+                "Invalid unstake receipt supplied!"
+            );
 
             let receipt_data = receipt
                 .as_non_fungible()
@@ -428,7 +976,7 @@ mod staking {
                 "You cannot unstake tokens before the redemption time."
             );
 
-            receipt.burn();
+            let receipt_data: UnstakeReceipt = self.burn_unstake_receipt(receipt);
 
             self.stakes
                 .get_mut(&receipt_data.address)
@@ -437,292 +985,2916 @@ mod staking {
                 .take(receipt_data.amount)
         }
 
-        // This method creates a new staking ID
+        // This method stakes the tokens of an unstake receipt back to a staking ID, whether the receipt has matured or not
         //
         // ## INPUT
-        // - none
+        // - `receipt`: the unstake receipt
+        // - `id_proof`: the proof of the staking ID
+        // - `address`: the address of the stakable token
         //
         // ## OUTPUT
-        // - the staking ID
+        // - none
         //
         // ## LOGIC
-        // - the method increments the ID counter
-        // - the method creates a new ID
-        // - the method returns the ID
-        pub fn create_id(&mut self) -> Bucket {
-            self.id_counter += 1;
+        // - the method checks the receipt and that it is for the stakable token, which has to be registered and active
+        // - the method checks the staking ID and that its rewards have been claimed
+        // - the method burns the receipt, freeing up an outstanding unstake slot on the staking ID that requested the unstake
+        // - the receipt's tokens, still in the stakable's vault, are added to the staking ID's stake, an unstake penalty already charged is not refunded
+        pub fn restake_receipt(&mut self, receipt: Bucket, id_proof: NonFungibleProof, address: ResourceAddress) {
+            assert!(
+                receipt.resource_address() == self.unstake_receipt_manager.address(),
+                "Invalid unstake receipt supplied!"
+            );
+            let receipt_data = receipt
+                .as_non_fungible()
+                .non_fungible::<UnstakeReceipt>()
+                .data();
+            assert!(receipt_data.address == address, "Receipt is not for this stakable.");
+            assert!(self.is_stakable(address), "Token is not stakable.");
 
-            let id_data = Id {
-                resources: HashMap::new(),
-                next_period: self.current_period + 1,
-            };
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            assert!(
+                id_data.next_period > self.current_period,
+                "Please claim unclaimed rewards on your ID before staking."
+            );
 
-            let id: Bucket = self
-                .id_manager
-                .mint_non_fungible(&NonFungibleLocalId::integer(self.id_counter), id_data);
+            let receipt_data: UnstakeReceipt = self.burn_unstake_receipt(receipt);
 
-            id
+            self.add_stake_to_id(&id, address, receipt_data.amount);
         }
 
-        // This method stakes tokens to a staking ID
+        // This method unstakes tokens immediately, skipping the unstake delay at the cost of the emergency penalty
         //
         // ## INPUT
-        // - `address`: the address of the stakable token
-        // - `stake_bucket`: an optional bucket of the staked tokens
         // - `id_proof`: the proof of the staking ID
-        // - `stake_transfer_receipt`: an optional stake transfer receipt
+        // - `address`: the address of the stakable token
+        // - `amount`: the amount of tokens to unstake
         //
         // ## OUTPUT
-        // - none
+        // - the unstaked tokens, minus the emergency penalty
         //
         // ## LOGIC
-        // - the method checks whether a staking ID is supplied, if not, it creates one
+        // - the method checks emergency unstaking is enabled and the requested amount is positive
         // - the method checks the staking ID
-        // - the method checks if latest rewards have been claimed, if not, the method fails
-        // - the method checks whether it received tokens or a transfer receipt
-        // - the method adds tokens to an internal vault, or burns the transfer receipt
-        // - the method updates the staking ID
-        pub fn stake(&mut self, stake_bucket: Bucket, id_proof: Option<Proof>) -> Option<Bucket> {
-            let id: NonFungibleLocalId;
-            let id_bucket: Option<Bucket> = None;
+        // - the method checks the requested tokens are staked and not locked
+        // - tokens are removed from staking ID stake and from the stakable's total stake
+        // - the emergency penalty is charged, going to the reward vault if the stakable is the reward token
+        // - the method returns the remaining tokens, no unstake receipt is minted
+        pub fn emergency_unstake(
+            &mut self,
+            id_proof: NonFungibleProof,
+            address: ResourceAddress,
+            amount: Decimal,
+        ) -> Bucket {
+            let emergency_penalty: Decimal = self
+                .emergency_penalty
+                .expect("Emergency unstaking is disabled.");
+            assert!(amount > dec!(0), "Unstake amount must be positive.");
 
-            if let Some(id_proof) = id_proof {
-                let id_proof =
-                    id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
-                id = id_proof.as_non_fungible().non_fungible::<Id>().local_id().clone();
-            } else {
-                let id_bucket = self.create_id();
-                id = id_bucket.as_non_fungible().non_fungible::<Id>().local_id().clone();
-            }
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
 
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
             let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+
+            let mut resource_map = id_data.resources.clone();
+            let mut resource = resource_map
+                .get(&address)
+                .expect("Stakable not found in staking ID.")
+                .clone();
+
             assert!(
-                id_data.next_period > self.current_period,
-                "Please claim unclaimed rewards on your ID before staking."
+                amount <= resource.amount_staked - resource.locked_amount(),
+                "You cannot unstake tokens currently participating in a vote."
             );
 
-            let stake_amount: Decimal;
-            let address: ResourceAddress;
+            resource.amount_staked -= amount;
 
-            if stake_bucket.resource_address() == self.stake_transfer_receipt_manager.address() {
-                (stake_amount, address) = self.stake_transfer_receipt(stake_bucket.as_non_fungible());
-            } else {
-                (stake_amount, address) = self.stake_tokens(stake_bucket);
-            }
+            self.stakes.get_mut(&address).unwrap().amount_staked -= amount;
+            self.recount_lock(address, &mut resource);
 
-            let mut resource_map = id_data.resources.clone();
-            resource_map.entry(address)
-                .and_modify(|resource| {
-                    resource.amount_staked += stake_amount;
-                })
-                .or_insert(Resource {
-                    amount_staked: stake_amount,
-                    locked_until: None,
-                });
+            resource_map.insert(address, resource);
 
             self.id_manager
                 .update_non_fungible_data(&id, "resources", resource_map);
 
-            self.stakes.get_mut(&address).unwrap().amount_staked += stake_amount;
-
-            self.id_manager.update_non_fungible_data(
-                &id,
-                "next_period",
-                self.current_period + 1,
-            );
+            let penalty: Decimal = self.collect_penalty(address, amount * emergency_penalty);
 
-            id_bucket
+            self.stakes
+                .get_mut(&address)
+                .unwrap()
+                .vault
+                .take(amount - penalty)
         }
 
-        // This method claims rewards from a staking ID
+        // This method converts a stake transfer receipt into an unstake receipt, so the tokens can be redeemed without a staking ID
         //
         // ## INPUT
-        // - `id_proof`: the proof of the staking ID
+        // - `receipt`: the stake transfer receipt
         //
         // ## OUTPUT
-        // - the claimed rewards
+        // - the unstake receipt
         //
         // ## LOGIC
-        // - the method updates the component period if necessary
-        // - the method checks the staking ID
-        // - the method checks amount of unclaimed periods
-        // - the method iterates over all staked tokens and calculates the rewards
-        // - the method updates the staking ID to the next period
-        // - the method returns the claimed rewards
-        pub fn update_id(&mut self, id_proof: NonFungibleProof) -> FungibleBucket {
-            self.update_period();
-            let id_proof =
-                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
-            let id = id_proof.non_fungible::<Id>().local_id().clone();
-            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+        // - the method checks the receipt
+        // - the method burns the transfer receipt
+        // - the unstake penalty is charged (waived shortly after a reward cut), as transferring would otherwise avoid it
+        // - the method mints an unstake receipt for the remaining tokens, redeemable after the unstake delay, or the stakable's tiered delay if it has one (see `unstake_delay_for`)
+        pub fn transfer_receipt_to_unstake(&mut self, receipt: NonFungibleBucket) -> Bucket {
+            assert!(
+                receipt.resource_address() == self.stake_transfer_receipt_manager.address(),
+                "Invalid stake transfer receipt supplied!"
+            );
+
+            let (amount, address) = self.stake_transfer_receipt(receipt);
+
+            let penalty: Decimal =
+                self.collect_penalty(address, amount * self.unstake_penalty_for(address));
+            self.stakes.get_mut(&address).unwrap().unstaking_amount += amount - penalty;
+
+            let unstake_receipt = UnstakeReceipt {
+                address,
+                amount: amount - penalty,
+                redemption_time: Clock::current_time_rounded_to_minutes()
+                    .add_days(self.unstake_delay_for(address, None))
+                    .unwrap(),
+                id: None,
+                queued_until: None,
+            };
+            self.unstake_receipt_counter = self
+                .unstake_receipt_counter
+                .checked_add(1)
+                .expect("Unstake receipt counter overflow.");
+            self.unstake_receipt_manager.mint_non_fungible(
+                &NonFungibleLocalId::integer(self.unstake_receipt_counter),
+                unstake_receipt,
+            )
+        }
+
+        // This method creates a new staking ID
+        //
+        // ## INPUT
+        // - none
+        //
+        // ## OUTPUT
+        // - the staking ID
+        //
+        // ## LOGIC
+        // - the method increments the ID counter
+        // - the method creates a new ID, able to claim from the current period on, so an ID created in the genesis period 0 claims it once it is recorded by the first period roll
+        // - the method returns the ID
+        pub fn create_id(&mut self) -> Bucket {
+            self.id_counter = self
+                .id_counter
+                .checked_add(1)
+                .expect("Staking ID counter overflow.");
+
+            let id_data = Id {
+                resources: HashMap::new(),
+                next_period: self.current_period + 1,
+                authorized_staker: None,
+                label: String::new(),
+                outstanding_unstakes: 0,
+                airdrops: HashMap::new(),
+                sweepable: false,
+                last_claim: None,
+                total_claimed: dec!(0),
+            };
+
+            let id: Bucket = self
+                .id_manager
+                .mint_non_fungible(&NonFungibleLocalId::integer(self.id_counter), id_data);
+
+            id
+        }
+
+        // This method stakes tokens to a staking ID
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `stake_bucket`: an optional bucket of the staked tokens
+        // - `id_proof`: the proof of the staking ID
+        // - `stake_transfer_receipt`: an optional stake transfer receipt
+        // - `whitelist_proof`: an optional proof of the whitelist badge, required to redeem a stake transfer receipt if a whitelist is set
+        //
+        // ## OUTPUT
+        // - the new staking ID, if none was supplied
+        //
+        // ## LOGIC
+        // - the method checks whether a staking ID is supplied, if not, it creates one
+        // - the method checks the staking ID
+        // - the method stakes the tokens or transfer receipt to the staking ID (see `stake_to_id`)
+        pub fn stake(
+            &mut self,
+            stake_bucket: Bucket,
+            id_proof: Option<Proof>,
+            whitelist_proof: Option<Proof>,
+        ) -> Option<Bucket> {
+            let (id, id_bucket) = self.id_or_new(id_proof);
+
+            self.stake_to_id(&id, stake_bucket, whitelist_proof);
+
+            id_bucket
+        }
+
+        // This method stakes tokens to a staking ID like `stake`, also returning the staking ID's updated data
+        //
+        // ## INPUT
+        // - `stake_bucket`: the staked tokens or a stake transfer receipt
+        // - `id_proof`: an optional proof of the staking ID
+        // - `whitelist_proof`: an optional proof of the whitelist badge, required to redeem a stake transfer receipt if a whitelist is set
+        //
+        // ## OUTPUT
+        // - the new staking ID, if none was supplied
+        // - the staking ID's data after staking
+        //
+        // ## LOGIC
+        // - the method checks whether a staking ID is supplied, if not, it creates one
+        // - the method stakes the tokens or transfer receipt to the staking ID (see `stake_to_id`)
+        // - the method returns the staking ID's updated data
+        pub fn stake_returning_id(
+            &mut self,
+            stake_bucket: Bucket,
+            id_proof: Option<Proof>,
+            whitelist_proof: Option<Proof>,
+        ) -> (Option<Bucket>, Id) {
+            let (id, id_bucket) = self.id_or_new(id_proof);
+
+            self.stake_to_id(&id, stake_bucket, whitelist_proof);
+
+            (id_bucket, self.id_manager.get_non_fungible_data(&id))
+        }
+
+        // This method creates a new staking ID and stakes tokens to it
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `stake_bucket`: the staked tokens
+        //
+        // ## OUTPUT
+        // - the new staking ID, holding the staked tokens
+        //
+        // ## LOGIC
+        // - the method checks the staked tokens are of the stakable token
+        // - the method creates a new staking ID
+        // - the method stakes the tokens to the staking ID (see `stake_to_id`)
+        pub fn stake_new(&mut self, address: ResourceAddress, stake_bucket: Bucket) -> Bucket {
+            assert!(
+                stake_bucket.resource_address() == address,
+                "Staked tokens do not match the stakable token."
+            );
+
+            let id_bucket: Bucket = self.create_id();
+            let id: NonFungibleLocalId = id_bucket
+                .as_non_fungible()
+                .non_fungible::<Id>()
+                .local_id()
+                .clone();
+
+            self.stake_to_id(&id, stake_bucket, None);
+
+            id_bucket
+        }
+
+        // This method stakes tokens to a staking ID on behalf of its owner
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        // - `stake_bucket`: the staked tokens or a stake transfer receipt
+        // - `staker_proof`: a proof of the badge the staking ID's owner authorized to stake for them
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the staking ID has an authorized staker and the proof matches it
+        // - the method stakes the tokens or transfer receipt to the staking ID (see `stake_to_id`)
+        pub fn stake_for(&mut self, id: NonFungibleLocalId, stake_bucket: Bucket, staker_proof: Proof) {
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            let authorized_staker: ResourceAddress = id_data
+                .authorized_staker
+                .expect("No authorized staker set for this ID.");
+            staker_proof.check_with_message(authorized_staker, "Invalid staker badge supplied!");
+
+            self.stake_to_id(&id, stake_bucket, None);
+        }
+
+        // This method sets the badge authorized to stake to a staking ID on behalf of its owner
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `authorized_staker`: the badge authorized to stake, or none to revoke authorization
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the staking ID
+        // - the method updates the staking ID's authorized staker
+        pub fn set_authorized_staker(
+            &mut self,
+            id_proof: NonFungibleProof,
+            authorized_staker: Option<ResourceAddress>,
+        ) {
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+
+            self.id_manager
+                .update_non_fungible_data(&id, "authorized_staker", authorized_staker);
+        }
+
+        // This method sets the label of a staking ID, letting users tell their staking IDs apart
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `label`: the new label
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the label is not too long
+        // - the method checks the staking ID
+        // - the method updates the staking ID's label
+        pub fn set_label(&mut self, id_proof: NonFungibleProof, label: String) {
+            assert!(label.len() <= MAX_LABEL_LENGTH, "Label is too long.");
+
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+
+            self.id_manager.update_non_fungible_data(&id, "label", label);
+        }
+
+        // This method lets a staking ID opt in or out of having its rewards swept by the treasury
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `sweepable`: whether the treasury may claim the staking ID's rewards
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the staking ID
+        // - the method updates the staking ID's sweepable flag
+        pub fn set_sweepable(&mut self, id_proof: NonFungibleProof, sweepable: bool) {
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+
+            self.id_manager.update_non_fungible_data(&id, "sweepable", sweepable);
+        }
+
+        // This method claims the rewards of a staking ID that opted in to sweeping, on behalf of the treasury
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        //
+        // ## OUTPUT
+        // - the claimed rewards, never vesting
+        //
+        // ## LOGIC
+        // - the method checks the staking ID opted in to sweeping
+        // - the method updates the component period if necessary
+        // - the method claims the rewards of all unclaimed periods (see `claim_rewards`), failing if there are none
+        // - the method returns the claimed rewards to the treasury
+        // - the claimed airdrops are not the treasury's, they are left on the staking ID and paid out to its holder along with their next claim
+        pub fn sweep_claim(&mut self, id: NonFungibleLocalId) -> FungibleBucket {
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            assert!(id_data.sweepable, "Staking ID has not opted in to sweeping.");
+
+            self.roll_period();
+            let staking_reward: Decimal = self
+                .claim_rewards(&id, None)
+                .expect("Wait longer to claim your rewards.");
+
+            self.total_rewards_distributed += staking_reward;
+            self.reward_vault.take(staking_reward)
+        }
+
+        // This method claims rewards from a staking ID
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        //
+        // ## OUTPUT
+        // - the claimed rewards, or a vesting receipt for them if vesting is enabled, followed by the claimed airdrops
+        //
+        // ## LOGIC
+        // - the method updates the component period if necessary, so a period that just ended is claimed even if no keeper has rolled it yet
+        // - the method checks the staking ID
+        // - the method claims the rewards of all unclaimed periods (see `claim_rewards`), failing if there are none
+        // - the method returns the claimed rewards, or a vesting receipt for them if vesting is enabled, and all airdrops claimed by the staking ID
+        pub fn update_id(&mut self, id_proof: NonFungibleProof) -> Vec<Bucket> {
+            self.roll_period();
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let staking_reward: Decimal = self
+                .claim_rewards(&id, None)
+                .expect("Wait longer to claim your rewards.");
+
+            let mut payout: Vec<Bucket> = vec![self.pay_rewards(staking_reward)];
+            payout.extend(self.pay_airdrops(&id));
+            payout
+        }
+
+        // This method claims rewards from a staking ID, processing at most a given amount of periods
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `max_periods`: the maximum amount of periods to claim
+        //
+        // ## OUTPUT
+        // - the claimed rewards, or a vesting receipt for them if vesting is enabled, followed by the claimed airdrops
+        //
+        // ## LOGIC
+        // - the method updates the component period if necessary
+        // - the method checks the staking ID
+        // - the method claims the rewards of the oldest unclaimed periods, at most `max_periods` (see `claim_rewards`), failing if there are none
+        // - the method returns the claimed rewards, or a vesting receipt for them if vesting is enabled, and all airdrops claimed by the staking ID
+        pub fn update_id_capped(&mut self, id_proof: NonFungibleProof, max_periods: i64) -> Vec<Bucket> {
+            assert!(max_periods > 0, "Maximum amount of periods must be positive.");
+
+            self.roll_period();
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let staking_reward: Decimal = self
+                .claim_rewards(&id, Some(max_periods))
+                .expect("Wait longer to claim your rewards.");
+
+            let mut payout: Vec<Bucket> = vec![self.pay_rewards(staking_reward)];
+            payout.extend(self.pay_airdrops(&id));
+            payout
+        }
+
+        // This method claims rewards from a staking ID and swaps them to another token through a DEX component
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        // - `dex`: the DEX component, which needs a `swap` method taking the input bucket and the target resource address, returning a bucket of the target resource
+        // - `min_out`: the minimum amount of the target token to receive
+        // - `target`: the resource address of the token to swap to
+        //
+        // ## OUTPUT
+        // - the swapped rewards
+        //
+        // ## LOGIC
+        // - the method checks rewards don't vest
+        // - the method updates the component period if necessary
+        // - the method checks the staking ID
+        // - the method claims the rewards of all unclaimed periods (see `claim_rewards`), failing if there are none
+        // - the method calls the DEX component's `swap` method with the claimed rewards
+        // - the method checks the DEX returned the target token and at least `min_out` of it
+        // - the method returns the swapped rewards, airdrops are left on the staking ID
+        pub fn update_id_and_swap(
+            &mut self,
+            id_proof: NonFungibleProof,
+            dex: Global<AnyComponent>,
+            min_out: Decimal,
+            target: ResourceAddress,
+        ) -> Bucket {
+            assert!(self.vesting_duration == 0, "Rewards cannot be swapped while they vest.");
+            assert!(min_out >= dec!(0), "Minimum output cannot be negative.");
+
+            self.roll_period();
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let staking_reward: Decimal = self
+                .claim_rewards(&id, None)
+                .expect("Wait longer to claim your rewards.");
+
+            let rewards: Bucket = self.pay_rewards(staking_reward);
+            let swapped: Bucket = dex.call_raw::<Bucket>("swap", scrypto_args!(rewards, target));
+
+            assert!(
+                swapped.resource_address() == target,
+                "DEX returned a different token than requested."
+            );
+            assert!(swapped.amount() >= min_out, "Swap output below minimum.");
+
+            swapped
+        }
+
+        // This method claims rewards from a staking ID and stakes them to it, adding a bonus, if the reward token is stakable
+        //
+        // ## INPUT
+        // - `id_proof`: the proof of the staking ID
+        //
+        // ## OUTPUT
+        // - the claimed airdrops
+        //
+        // ## LOGIC
+        // - the method checks the reward token is stakable and rewards don't vest
+        // - the method updates the component period if necessary
+        // - the method checks the staking ID
+        // - the method claims the rewards of all unclaimed periods (see `claim_rewards`), failing if there are none
+        // - the method adds the reward token's compound bonus, as far as the reward vault holds unreserved rewards
+        // - the method stakes the rewards and bonus to the staking ID, restarting the reward token's eligibility delay
+        // - the method returns all airdrops claimed by the staking ID
+        pub fn compound_rewards(&mut self, id_proof: NonFungibleProof) -> Vec<Bucket> {
+            let reward_address: ResourceAddress = self.reward_vault.resource_address();
+            assert!(self.is_stakable(reward_address), "Reward token is not stakable.");
+            assert!(self.vesting_duration == 0, "Rewards cannot be compounded while they vest.");
+
+            self.roll_period();
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let staking_reward: Decimal = self
+                .claim_rewards(&id, None)
+                .expect("Wait longer to claim your rewards.");
+
+            let unreserved: Decimal =
+                (self.reward_vault.amount() - self.reserved_rewards - staking_reward).max(dec!(0));
+            let bonus: Decimal = (staking_reward
+                * self.stakes.get(&reward_address).unwrap().compound_bonus)
+                .min(unreserved);
+
+            self.total_rewards_distributed += staking_reward + bonus;
+            let rewards: FungibleBucket = self.reward_vault.take(staking_reward + bonus);
+            let (stake_amount, address) = self.stake_tokens(rewards.into());
+            self.add_stake_to_id(&id, address, stake_amount);
+
+            self.pay_airdrops(&id)
+        }
+
+        // This method locks staked tokens for a certain duration and gives rewards for locking them
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `id_proof`: the proof of the staking ID
+        //
+        // ## OUTPUT
+        // - rewards for locking the tokens
+        //
+        // ## LOGIC
+        // - the method checks the staking ID
+        // - the method checks whether this resource address is lockable
+        // - the method checks whether the staking ID has claimed its rewards, so the lock does not change the rewards of earlier periods
+        // - the method checks whether the staking ID has staked tokens that are not locked yet
+        // - the method locks all staked tokens by updating the staking ID, an existing lock ending later than the new one is kept, so a lock is never shortened
+        // - a DAO lock stays in place until it ends, even if the new lock ends later, so it cannot be escaped by locking
+        // - the staking ID records the lock payment, to be refunded when force unlocking, starting over if its previous lock has ended
+        // - the lock's reward multiplier weights the locked tokens' staking rewards for every period the lock fully covers (see `count_lock`)
+        // - the method returns the rewards for locking the newly locked tokens, taken from the lock reward vault
+
+
+        pub fn lock_stake(&mut self, address: ResourceAddress, id_proof: NonFungibleProof) -> FungibleBucket {
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+            let lock: Lock = self.stakes.get(&address).expect("Stakable not found.").lock.clone();
+            assert!(lock.duration > 0, "Stakable is not lockable.");
+
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            assert!(
+                id_data.next_period > self.current_period,
+                "Please claim unclaimed rewards on your ID before locking."
+            );
+            let mut resource_map = id_data.resources.clone();
+            let mut resource = resource_map
+                .get(&address)
+                .expect("Stakable not found in staking ID.")
+                .clone();
+
+            let amount_staked = resource.amount_staked;
+            let newly_locked: Decimal = amount_staked - resource.locked_amount();
+            assert!(newly_locked > dec!(0), "Tokens are already locked.");
+
+            if resource.locked_amount() == dec!(0) {
+                resource.lock_payment = dec!(0);
+            }
+
+            let mut lock_until: Instant = Clock::current_time_rounded_to_minutes().add_days(lock.duration).unwrap();
+            if let Some(locked_until) = resource.locked_until {
+                if locked_until.seconds_since_unix_epoch > lock_until.seconds_since_unix_epoch {
+                    lock_until = locked_until;
+                }
+            }
+            resource.locked_until = Some(lock_until);
+            resource.amount_locked = amount_staked;
+            let lock_end_period: i64 = self.lock_end_period(lock_until);
+            self.count_lock(address, &mut resource, amount_staked, lock.multiplier, lock_end_period);
+
+            let lock_reward: Decimal = lock.payment * newly_locked;
+            resource.lock_payment += lock_reward;
+            resource_map.insert(address, resource);
+
+            self.id_manager
+                .update_non_fungible_data(&id, "resources", resource_map);
+
+            if self.lock_reward_vault.resource_address() == self.reward_vault.resource_address() {
+                self.total_rewards_distributed += lock_reward;
+            }
+
+            self.lock_reward_vault.take(lock_reward)
+        }
+
+        // This method unlocks locked tokens before their lock expires, at the cost of the force unlock penalty and refunding the lock payment
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `id_proof`: the proof of the staking ID
+        // - `amount`: the amount of locked tokens to unlock
+        // - `refund`: lock reward tokens to refund the lock payment with
+        //
+        // ## OUTPUT
+        // - the lock reward tokens left over after the refund
+        //
+        // ## LOGIC
+        // - the method checks force unlocking is enabled
+        // - the method checks the staking ID
+        // - the method checks the tokens are not locked by a DAO vote and the amount is positive and locked
+        // - the method unlocks the tokens, clearing the lock if no locked tokens remain
+        // - the lock payment received for the unlocked tokens is refunded pro-rata to the lock reward vault, rounded up to the lock reward token's divisibility
+        // - the force unlock penalty is charged from the unlocked tokens, going to the reward vault if the stakable is the reward token
+        // - the unlocked tokens no longer earn the lock multiplier (see `count_lock`)
+        pub fn force_unlock(
+            &mut self,
+            address: ResourceAddress,
+            id_proof: NonFungibleProof,
+            amount: Decimal,
+            mut refund: FungibleBucket,
+        ) -> FungibleBucket {
+            let force_unlock_penalty: Decimal = self
+                .force_unlock_penalty
+                .expect("Force unlocking is disabled.");
+            assert!(
+                refund.resource_address() == self.lock_reward_vault.resource_address(),
+                "Refund must be paid in the lock reward token."
+            );
+
+            let id_proof =
+                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+            let id = id_proof.non_fungible::<Id>().local_id().clone();
+
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            let mut resource_map = id_data.resources.clone();
+            let mut resource = resource_map
+                .get(&address)
+                .expect("Stakable not found in staking ID.")
+                .clone();
+
+            assert!(!resource.dao_locked(), "Tokens locked by a DAO vote cannot be force unlocked.");
+            let locked_amount: Decimal = resource.locked_amount();
+            assert!(
+                amount > dec!(0) && amount <= locked_amount,
+                "Amount must be positive and no more than the locked amount."
+            );
+
+            let refunded: FungibleBucket = refund.take_advanced(
+                resource.lock_payment * amount / locked_amount,
+                WithdrawStrategy::Rounded(RoundingMode::AwayFromZero),
+            );
+            resource.lock_payment = (resource.lock_payment - refunded.amount()).max(dec!(0));
+            if self.lock_reward_vault.resource_address() == self.reward_vault.resource_address() {
+                self.total_rewards_distributed -= refunded.amount();
+            }
+            self.lock_reward_vault.put(refunded);
+
+            resource.amount_locked = locked_amount - amount;
+            if resource.amount_locked == dec!(0) {
+                resource.locked_until = None;
+                resource.lock_payment = dec!(0);
+            }
+
+            let penalty: Decimal = self.collect_penalty(address, amount * force_unlock_penalty);
+            resource.amount_staked -= penalty;
+            self.stakes.get_mut(&address).unwrap().amount_staked -= penalty;
+
+            let counted_locked: Decimal = resource
+                .counted_locked
+                .min(resource.amount_locked)
+                .min(resource.amount_staked);
+            let (lock_multiplier, lock_end_period) = (resource.lock_multiplier, resource.lock_end_period);
+            self.count_lock(address, &mut resource, counted_locked, lock_multiplier, lock_end_period);
+            resource_map.insert(address, resource);
+
+            self.id_manager
+                .update_non_fungible_data(&id, "resources", resource_map);
+
+            refund
+        }
+
+        // This method brings the stakables' total locked amounts up to date with a staking ID's stake
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - for every token staked by the staking ID, the staking ID's counted locked tokens are capped at its staked amount (see `recount_lock`)
+        // - expired locks leave the stakables' locked totals by themselves once their last period is recorded, so for up-to-date bookkeeping this changes nothing
+        // - anyone can call this method, as it only corrects bookkeeping
+        pub fn sync_locks(&mut self, id: NonFungibleLocalId) {
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            let mut resource_map = id_data.resources.clone();
+
+            for (address, resource) in resource_map.iter_mut() {
+                self.recount_lock(*address, resource);
+            }
+
+            self.id_manager
+                .update_non_fungible_data(&id, "resources", resource_map);
+        }
+
+        // This method redeems the vested part of a reward vesting receipt
+        //
+        // ## INPUT
+        // - `receipt`: the reward vesting receipt
+        //
+        // ## OUTPUT
+        // - the rewards vested since the last redemption
+        // - the receipt, if its rewards have not fully vested yet
+        //
+        // ## LOGIC
+        // - the method checks the receipt
+        // - the method calculates the vested amount, which increases linearly from the vesting start to the vesting end
+        // - the method takes the vested amount not yet claimed from the vesting vault
+        // - if all rewards have vested, the method burns the receipt, otherwise it records the claimed amount and returns the receipt
+        pub fn claim_vested(&mut self, receipt: Bucket) -> (FungibleBucket, Option<Bucket>) {
+            assert!(
+                receipt.resource_address() == self.vesting_receipt_manager.address(),
+                "Invalid vesting receipt supplied!"
+            );
+
+            let receipt_nft = receipt
+                .as_non_fungible()
+                .non_fungible::<RewardVestingReceipt>();
+            let receipt_id = receipt_nft.local_id().clone();
+            let receipt_data = receipt_nft.data();
+
+            let now: i64 = Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch;
+            let vest_start: i64 = receipt_data.vest_start.seconds_since_unix_epoch;
+            let vest_end: i64 = receipt_data.vest_end.seconds_since_unix_epoch;
+
+            let vested_amount: Decimal = if now >= vest_end {
+                receipt_data.amount
+            } else if now <= vest_start {
+                dec!(0)
+            } else {
+                receipt_data.amount * Decimal::from(now - vest_start)
+                    / Decimal::from(vest_end - vest_start)
+            };
+
+            let rewards: FungibleBucket = self
+                .vesting_vault
+                .take(vested_amount - receipt_data.amount_claimed);
+
+            if vested_amount == receipt_data.amount {
+                receipt.burn();
+                (rewards, None)
+            } else {
+                self.vesting_receipt_manager.update_non_fungible_data(
+                    &receipt_id,
+                    "amount_claimed",
+                    vested_amount,
+                );
+                (rewards, Some(receipt))
+            }
+        }
+
+        //////////////////////////////////////////////////////////////////////
+        ////////////////////////////GETTER METHODS////////////////////////////
+        //////////////////////////////////////////////////////////////////////
+
+        // This method returns the mint counters of the unstake and stake transfer receipts
+        //
+        // ## OUTPUT
+        // - the amount of unstake receipts minted so far
+        // - the amount of stake transfer receipts minted so far
+        //
+        // ## LOGIC
+        // - the counters are only ever incremented, so they count all minted receipts, not just outstanding ones
+        pub fn receipt_counters(&self) -> (u64, u64) {
+            (self.unstake_receipt_counter, self.stake_transfer_receipt_counter)
+        }
+
+        // This method returns the integer IDs the next unstake and stake transfer receipts will be minted with
+        //
+        // ## OUTPUT
+        // - the ID of the next unstake receipt
+        // - the ID of the next stake transfer receipt
+        //
+        // ## LOGIC
+        // - receipts are minted with the incremented counter as ID, so the next ID is the counter plus one
+        // - this is a best-effort prediction, another transaction minting a receipt first takes the predicted ID
+        pub fn next_receipt_ids(&self) -> (u64, u64) {
+            (
+                self.unstake_receipt_counter + 1,
+                self.stake_transfer_receipt_counter + 1,
+            )
+        }
+
+        // This method returns the token and amount of an unstake or stake transfer receipt
+        //
+        // ## INPUT
+        // - `receipt_id`: the local id of the receipt
+        // - `is_unstake`: whether the receipt is an unstake receipt, otherwise it is a stake transfer receipt
+        //
+        // ## OUTPUT
+        // - the address of the receipt's token and the amount of it
+        pub fn receipt_info(&self, receipt_id: NonFungibleLocalId, is_unstake: bool) -> (ResourceAddress, Decimal) {
+            if is_unstake {
+                let receipt_data: UnstakeReceipt =
+                    self.unstake_receipt_manager.get_non_fungible_data(&receipt_id);
+                (receipt_data.address, receipt_data.amount)
+            } else {
+                let receipt_data: StakeTransferReceipt =
+                    self.stake_transfer_receipt_manager.get_non_fungible_data(&receipt_id);
+                (receipt_data.address, receipt_data.amount)
+            }
+        }
+
+        // This method returns the time from which an unstake receipt can be redeemed
+        pub fn unstake_redemption_time(&self, receipt_id: NonFungibleLocalId) -> Instant {
+            let receipt_data: UnstakeReceipt =
+                self.unstake_receipt_manager.get_non_fungible_data(&receipt_id);
+            receipt_data.redemption_time
+        }
+
+        // This method returns the token, amount and redemption time of several unstake receipts
+        //
+        // ## INPUT
+        // - `receipt_ids`: the local ids of the unstake receipts, at most MAX_PAGE_SIZE
+        //
+        // ## OUTPUT
+        // - the local id, token address, amount and redemption time of every receipt
+        pub fn unstake_receipts_info(
+            &self,
+            receipt_ids: Vec<NonFungibleLocalId>,
+        ) -> Vec<(NonFungibleLocalId, ResourceAddress, Decimal, Instant)> {
+            assert!(receipt_ids.len() as u64 <= MAX_PAGE_SIZE, "Too many receipts requested.");
+
+            receipt_ids
+                .into_iter()
+                .map(|receipt_id| {
+                    let receipt_data: UnstakeReceipt =
+                        self.unstake_receipt_manager.get_non_fungible_data(&receipt_id);
+                    (
+                        receipt_id,
+                        receipt_data.address,
+                        receipt_data.amount,
+                        receipt_data.redemption_time,
+                    )
+                })
+                .collect()
+        }
+
+        // This method returns the reward a staking ID claimed for a period
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        // - `period`: the period
+        //
+        // ## OUTPUT
+        // - the claimed reward, or none if the staking ID has not claimed this period
+        pub fn claim_history(&self, id: NonFungibleLocalId, period: i64) -> Option<Decimal> {
+            self.claimed_rewards.get(&(id, period)).map(|reward| *reward)
+        }
+
+        // This method returns the total staked amount of a stakable during a recorded period
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `period`: the period
+        //
+        // ## OUTPUT
+        // - the total staked amount, or none if the period has not been recorded or the token was not stakable then
+        pub fn historical_staked(&self, address: ResourceAddress, period: i64) -> Option<Decimal> {
+            self.staked_history.get(&(address, period)).map(|staked| *staked)
+        }
+
+        // This method returns how many periods of a stakable's rewards the reward vault can still fund, not counting rewards already owed to stakers
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        //
+        // ## OUTPUT
+        // - the amount of fully funded periods, or i64::MAX if the stakable emits no rewards
+        pub fn stakable_runway(&self, address: ResourceAddress) -> i64 {
+            let reward_amount: Decimal = self
+                .stakes
+                .get(&address)
+                .expect("Stakable not found.")
+                .emission()
+                * self.global_reward_multiplier;
+
+            if reward_amount <= dec!(0) {
+                return i64::MAX;
+            }
+
+            let available: Decimal = (self.reward_vault.amount() - self.reserved_rewards).max(dec!(0));
+            let runway_dec: Decimal = (available / reward_amount)
+                .checked_floor()
+                .unwrap();
+
+            i64::try_from(runway_dec.0 / Decimal::ONE.0).unwrap()
+        }
+
+        // This method returns the staked balance of a token for a range of staking IDs
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `start`: the first integer staking ID of the range
+        // - `end`: the integer staking ID after the last one of the range
+        //
+        // ## OUTPUT
+        // - the staking IDs in the range and their staked balance of the token
+        //
+        // ## LOGIC
+        // - the method checks the range is no larger than MAX_PAGE_SIZE
+        // - the method reads every minted staking ID in the range and returns its staked balance
+        pub fn ids_in_range(
+            &self,
+            address: ResourceAddress,
+            start: u64,
+            end: u64,
+        ) -> Vec<(NonFungibleLocalId, Decimal)> {
+            assert!(start <= end, "Range start cannot be after its end.");
+            assert!(end - start <= MAX_PAGE_SIZE, "Range exceeds the maximum page size.");
+
+            let mut balances: Vec<(NonFungibleLocalId, Decimal)> = vec![];
+
+            for number in start.max(1)..end.min(self.id_counter + 1) {
+                let id = NonFungibleLocalId::integer(number);
+                let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+                let amount_staked: Decimal = id_data
+                    .resources
+                    .get(&address)
+                    .map_or(dec!(0), |resource| resource.amount_staked);
+                balances.push((id, amount_staked));
+            }
+
+            balances
+        }
+
+        // This method returns the name and symbol of the project used in the metadata of the component's resources
+        pub fn branding(&self) -> (String, String) {
+            (self.name.clone(), self.symbol.clone())
+        }
+
+        // This method returns the address of the reward token and the amount of it available in the reward vault
+        pub fn reward_info(&self) -> (ResourceAddress, Decimal) {
+            (self.reward_vault.resource_address(), self.reward_vault.amount())
+        }
+
+        // This method returns the amount of a stakable token held by outstanding unstake receipts, waiting to be redeemed
+        pub fn unstaking_total(&self, address: ResourceAddress) -> Decimal {
+            self.stakes
+                .get(&address)
+                .expect("Stakable not found.")
+                .unstaking_amount
+        }
+
+        // This method returns whether a token can currently be staked, meaning it is registered and active
+        pub fn is_stakable(&self, address: ResourceAddress) -> bool {
+            self.stakes
+                .get(&address)
+                .map_or(false, |stakable| stakable.active)
+        }
+
+        // This method returns the vault balance and the total staked amount of a stakable token, the vault also holds tokens of outstanding unstake and transfer receipts
+        pub fn stakable_balance(&self, address: ResourceAddress) -> (Decimal, Decimal) {
+            let stakable = self.stakes.get(&address).expect("Stakable not found.");
+            (stakable.vault.amount(), stakable.amount_staked)
+        }
+
+        // This method returns the reward amount per period of every stakable token
+        pub fn all_reward_rates(&self) -> Vec<(ResourceAddress, Decimal)> {
+            self.stakes
+                .iter()
+                .map(|(address, stakable)| (*address, stakable.reward_amount))
+                .collect()
+        }
+
+        // This method returns the reward per staked token the current period would record if it ended now, zero if nothing is staked
+        pub fn current_reward_rate(&self, address: ResourceAddress) -> Decimal {
+            let stakable = self.stakes.get(&address).expect("Stakable not found.");
+            let emission: Decimal =
+                stakable.budgeted_emission(self.current_period, self.global_reward_multiplier);
+
+            if emission == dec!(0) {
+                return dec!(0);
+            }
+
+            emission / stakable.reward_base()
+        }
+
+        // This method returns the reward amount per period and the lock configuration of every stakable token
+        pub fn all_stakable_configs(&self) -> Vec<(ResourceAddress, Decimal, Lock)> {
+            self.stakes
+                .iter()
+                .map(|(address, stakable)| (*address, stakable.reward_amount, stakable.lock.clone()))
+                .collect()
+        }
+
+        // This method returns the staked balance of a staking ID for every stakable token
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        //
+        // ## OUTPUT
+        // - the address of every stakable token and the staking ID's staked balance of it, zero if never staked
+        pub fn staked_balances(&self, id: NonFungibleLocalId) -> Vec<(ResourceAddress, Decimal)> {
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+
+            self.stakes
+                .keys()
+                .map(|address| {
+                    (
+                        *address,
+                        id_data
+                            .resources
+                            .get(address)
+                            .map_or(dec!(0), |resource| resource.amount_staked),
+                    )
+                })
+                .collect()
+        }
+
+        // This method returns the next period stored on a staking ID, rewards are claimable from the period before it on
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        //
+        // ## OUTPUT
+        // - the staking ID's next period, current period + 1 if it has claimed all its rewards
+        pub fn id_next_period(&self, id: NonFungibleLocalId) -> i64 {
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            id_data.next_period
+        }
+
+        // This method returns the rewards a staking ID can currently claim, without claiming them
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        //
+        // ## OUTPUT
+        // - the claimable rewards over all recorded unclaimed periods, periods not yet rolled are not included
+        //
+        // ## LOGIC
+        // - the rewards are calculated as a claim would calculate them (see `calculate_rewards`)
+        // - zero is returned while the staking ID's claim cooldown has not passed, as a claim would fail
+        // - the rewards are capped by the maximum lifetime reward, as a claim would cap them
+        pub fn preview_rewards(&self, id: NonFungibleLocalId) -> Decimal {
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+
+            if !self.claim_cooldown_passed(&id_data) {
+                return dec!(0);
+            }
+
+            self.calculate_rewards(&id_data, None)
+                .map_or(dec!(0), |(_, period_rewards, _)| {
+                    let rewards: Decimal = period_rewards
+                        .iter()
+                        .fold(dec!(0), |total, period_reward| total + *period_reward);
+                    self.cap_lifetime_reward(&id_data, rewards)
+                })
+        }
+
+        // This method returns the rewards several staking IDs can currently claim combined, without claiming them
+        //
+        // ## INPUT
+        // - `ids`: the staking IDs, at most MAX_PAGE_SIZE
+        //
+        // ## OUTPUT
+        // - the sum of the claimable rewards of the staking IDs (see `preview_rewards`)
+        pub fn total_claimable(&self, ids: Vec<NonFungibleLocalId>) -> Decimal {
+            assert!(ids.len() as u64 <= MAX_PAGE_SIZE, "Too many staking IDs requested.");
+
+            ids.into_iter()
+                .fold(dec!(0), |total, id| total + self.preview_rewards(id))
+        }
+
+        // This method returns whether a staking ID has any staked tokens that are currently locked
+        pub fn has_active_locks(&self, id: NonFungibleLocalId) -> bool {
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            id_data
+                .resources
+                .values()
+                .any(|resource| resource.locked_amount() > dec!(0))
+        }
+
+        // This method returns the fraction of a stakable's total stake that is locked
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        //
+        // ## OUTPUT
+        // - the locked amount divided by the total stake, zero if nothing is staked
+        //
+        // ## LOGIC
+        // - tokens locked by a reward lock or by a DAO vote count as locked (see `total_locked`)
+        // - a lock counts until the end of the last period it fully covers
+        pub fn lock_participation(&self, address: ResourceAddress) -> Decimal {
+            let stakable = self.stakes.get(&address).expect("Stakable not found.");
+
+            if stakable.amount_staked == dec!(0) {
+                return dec!(0);
+            }
+
+            (stakable.locked_amount + stakable.dao_locked_amount).min(stakable.amount_staked)
+                / stakable.amount_staked
+        }
+
+        // This method returns the total amount of a stakable locked by a reward lock or by a DAO vote, a lock counts until the end of the last period it fully covers
+        pub fn total_locked(&self, address: ResourceAddress) -> Decimal {
+            let stakable = self.stakes.get(&address).expect("Stakable not found.");
+            stakable.locked_amount + stakable.dao_locked_amount
+        }
+
+        // This method returns the share a staking ID holds of a stakable's total stake
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        // - `address`: the address of the stakable token
+        //
+        // ## OUTPUT
+        // - the staking ID's stake divided by the stakable's total stake, zero if nothing is staked
+        pub fn stake_share(&self, id: NonFungibleLocalId, address: ResourceAddress) -> Decimal {
+            let total_staked: Decimal = self
+                .stakes
+                .get(&address)
+                .expect("Stakable not found.")
+                .amount_staked;
+
+            if total_staked == dec!(0) {
+                return dec!(0);
+            }
+
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+            id_data
+                .resources
+                .get(&address)
+                .map_or(dec!(0), |resource| resource.amount_staked)
+                / total_staked
+        }
+
+        // This method returns the reward a staking ID would receive for locking a stakable token, without locking it
+        //
+        // ## INPUT
+        // - `id`: the staking ID
+        // - `address`: the address of the stakable token
+        //
+        // ## OUTPUT
+        // - the lock reward, only paid for staked tokens that are not locked yet, as in lock_stake
+        pub fn preview_lock_reward(&self, id: NonFungibleLocalId, address: ResourceAddress) -> Decimal {
+            let stakable = self.stakes.get(&address).expect("Stakable not found.");
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+
+            id_data
+                .resources
+                .get(&address)
+                .map_or(dec!(0), |resource| {
+                    stakable.lock.payment * (resource.amount_staked - resource.locked_amount())
+                })
+        }
+
+        // This method returns the seconds until the next period starts, zero if it has already started but not been rolled yet
+        pub fn seconds_to_next_period(&self) -> i64 {
+            (self.next_period.seconds_since_unix_epoch
+                - Clock::current_time_rounded_to_minutes().seconds_since_unix_epoch)
+                .max(0)
+        }
+
+        // This method returns the current unstake delay and the maximum unstake delay the admin can set, both in days
+        pub fn unstake_delay_info(&self) -> (i64, i64) {
+            (self.unstake_delay, self.max_unstaking_delay)
+        }
+
+        // This method returns the locking options of a stakable token
+        pub fn get_lock_config(&self, address: ResourceAddress) -> Lock {
+            self.stakes
+                .get(&address)
+                .expect("Stakable not found.")
+                .lock
+                .clone()
+        }
+
+        // This method returns the lock duration in days and the lock payment per locked token of a stakable token
+        pub fn lock_terms(&self, address: ResourceAddress) -> (i64, Decimal) {
+            let lock: &Lock = &self.stakes.get(&address).expect("Stakable not found.").lock;
+            (lock.duration, lock.payment)
+        }
+
+        // This method returns the unspent reward budget of a stakable, if none, its rewards are not budgeted
+        pub fn reward_budget(&self, address: ResourceAddress) -> Option<Decimal> {
+            self.stakes
+                .get(&address)
+                .expect("Stakable not found.")
+                .reward_budget
+        }
+
+        // This method returns the address of the controller badge owning the staking component
+        pub fn get_controller(&self) -> ResourceAddress {
+            self.controller
+        }
+
+        // This method returns whether a DAO controls the staking, meaning the owner can lock staked tokens through set_lock
+        pub fn is_dao_controlled(&self) -> bool {
+            self.dao_controlled
+        }
+
+        // This method returns the setup of the staking component, to verify a deployed component matches expectations
+        pub fn setup_info(&self) -> SetupInfo {
+            SetupInfo {
+                period_interval: self.period_interval,
+                max_unstaking_delay: self.max_unstaking_delay,
+                max_lock_duration: self.max_lock_duration,
+                dao_controlled: self.dao_controlled,
+                reward_resource: self.reward_vault.resource_address(),
+                lock_reward_resource: self.lock_reward_vault.resource_address(),
+            }
+        }
+
+        // This method returns the total amount of staking rewards paid out to stakers, including lock rewards if they are paid in the reward token
+        pub fn total_distributed(&self) -> Decimal {
+            self.total_rewards_distributed
+        }
+
+        // This method checks for every stakable whether its vault holds at least the total staked amount
+        //
+        // ## OUTPUT
+        // - the address of every stakable token and whether its vault covers its total stake
+        //
+        // ## LOGIC
+        // - the vault may hold more than the total stake, as tokens being unstaked or transferred are not counted as staked
+        pub fn health_check(&self) -> Vec<(ResourceAddress, bool)> {
+            self.stakes
+                .iter()
+                .map(|(address, stakable_unit)| {
+                    (*address, stakable_unit.vault.amount() >= stakable_unit.amount_staked)
+                })
+                .collect()
+        }
+
+        // This method fails if the vault of any stakable holds less than its total staked amount
+        pub fn assert_solvent(&self) {
+            for (address, solvent) in self.health_check() {
+                assert!(solvent, "Stakable {:?} is insolvent.", address);
+            }
+        }
+
+        // This method returns whether the reward vault can fund the next period's rewards of all stakables on top of the rewards already owed to stakers
+        pub fn is_healthy(&self) -> bool {
+            let next_period_rewards: Decimal = self
+                .stakes
+                .values()
+                .filter(|stakable_unit| !stakable_unit.emissions_paused)
+                .fold(dec!(0), |total, stakable_unit| total + stakable_unit.emission())
+                * self.global_reward_multiplier;
+
+            self.reward_vault.amount() - self.reserved_rewards >= next_period_rewards
+        }
+
+        //////////////////////////////////////////////////////////////////////
+        ////////////////////////////ADMIN METHODS/////////////////////////////
+        //////////////////////////////////////////////////////////////////////
+
+        pub fn set_period_interval(&mut self, new_interval: i64) {
+            self.period_interval = new_interval;
+        }
+
+        pub fn fill_rewards(&mut self, bucket: Bucket) {
+            assert!(
+                bucket.resource_address() == self.reward_vault.resource_address(),
+                "Rewards must be the fungible reward token."
+            );
+            self.reward_vault.put(bucket.as_fungible());
+        }
+
+        // This method schedules an airdrop to the stakers of a stakable token during a period
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `period`: the period during which stakers earn the airdrop
+        // - `tokens`: the airdropped tokens
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the stakable exists, the period has not been recorded yet and has no airdrop scheduled for it yet
+        // - the method puts the tokens in the airdrop vault of their resource and allocates them to stakers
+        // - the method schedules the airdrop, which is divided over the stakers when the period is recorded
+        // - if nothing is staked when the period is recorded, the airdrop is not divided and its tokens are no longer allocated, recoverable through `remove_airdrop_tokens`
+        pub fn schedule_airdrop(&mut self, address: ResourceAddress, period: i64, tokens: Bucket) {
+            assert!(period >= self.current_period, "Period has already been recorded.");
+            let stakable = self.stakes.get(&address).expect("Stakable not found.");
+            assert!(
+                stakable.airdrop_schedule.get(&period).is_none(),
+                "Airdrop already scheduled for this period."
+            );
+
+            let resource: ResourceAddress = tokens.resource_address();
+            let amount: Decimal = tokens.amount();
+            assert!(amount > dec!(0), "Amount must be positive.");
+
+            if self.airdrop_vaults.get(&resource).is_none() {
+                self.airdrop_vaults.insert(resource, Vault::new(resource));
+            }
+            self.airdrop_vaults.get_mut(&resource).unwrap().put(tokens);
+            let allocated: Decimal = self.allocated_airdrops.get(&resource).map_or(dec!(0), |allocated| *allocated);
+            self.allocated_airdrops.insert(resource, allocated + amount);
+
+            stakable.airdrop_schedule.insert(period, (resource, amount));
+        }
+
+        pub fn remove_airdrop_tokens(&mut self, resource: ResourceAddress, amount: Decimal) -> Bucket {
+            let allocated: Decimal = self.allocated_airdrops.get(&resource).map_or(dec!(0), |allocated| *allocated);
+            let mut vault = self
+                .airdrop_vaults
+                .get_mut(&resource)
+                .expect("No airdrop vault for this resource.");
+            assert!(
+                amount <= vault.amount() - allocated,
+                "Cannot remove airdropped tokens allocated to stakers."
+            );
+            vault.take(amount)
+        }
+
+        pub fn remove_rewards(&mut self, amount: Decimal) -> Bucket {
+            assert!(amount > dec!(0), "Amount must be positive.");
+            assert!(
+                amount <= self.reward_vault.amount() - self.reserved_rewards,
+                "Cannot remove rewards owed to stakers."
+            );
+            self.reward_vault.take(amount).into()
+        }
+
+        // This method retires the reward token, stopping all rewards and withdrawing the reward vault
+        //
+        // ## INPUT
+        // - none
+        //
+        // ## OUTPUT
+        // - the full balance of the reward vault
+        //
+        // ## LOGIC
+        // - the method checks no rewards recorded for past periods are still unclaimed
+        // - the method sets the reward amount of every stakable to zero
+        // - the method returns the full balance of the reward vault
+        pub fn retire_reward_token(&mut self) -> Bucket {
+            assert!(
+                self.reserved_rewards == dec!(0),
+                "Unclaimed rewards are still owed to stakers."
+            );
+
+            let now: Instant = Clock::current_time_rounded_to_minutes();
+            for (_address, stakable_unit) in self.stakes.iter_mut() {
+                if stakable_unit.reward_amount > dec!(0) {
+                    stakable_unit.last_reward_cut = Some(now);
+                }
+                stakable_unit.reward_amount = dec!(0);
+            }
+
+            self.reward_vault.take_all().into()
+        }
+
+        pub fn fill_lock_rewards(&mut self, bucket: Bucket) {
+            assert!(
+                bucket.resource_address() == self.lock_reward_vault.resource_address(),
+                "Lock rewards must be the lock reward token."
+            );
+            self.lock_reward_vault.put(bucket.as_fungible());
+        }
+
+        pub fn remove_lock_rewards(&mut self, amount: Decimal) -> Bucket {
+            self.lock_reward_vault.take(amount).into()
+        }
+
+        pub fn set_round_down_rewards(&mut self, round_down_rewards: bool) {
+            self.round_down_rewards = round_down_rewards;
+        }
+
+        pub fn reclaim_dust(&mut self) -> Bucket {
+            self.dust_vault.take_all().into()
+        }
+
+        pub fn set_global_reward_multiplier(&mut self, global_reward_multiplier: Decimal) {
+            assert!(global_reward_multiplier >= dec!(0), "Global reward multiplier cannot be negative.");
+            self.global_reward_multiplier = global_reward_multiplier;
+        }
+
+        pub fn set_keeper_reward(&mut self, keeper_reward: Decimal) {
+            assert!(keeper_reward >= dec!(0), "Keeper reward cannot be negative.");
+            self.keeper_reward = keeper_reward;
+        }
+
+        pub fn set_max_lifetime_reward(&mut self, max_lifetime_reward: Option<Decimal>) {
+            if let Some(max_lifetime_reward) = max_lifetime_reward {
+                assert!(max_lifetime_reward >= dec!(0), "Maximum lifetime reward cannot be negative.");
+            }
+            self.max_lifetime_reward = max_lifetime_reward;
+        }
+
+        pub fn set_claim_cooldown(&mut self, claim_cooldown: i64) {
+            assert!(claim_cooldown >= 0, "Claim cooldown cannot be negative.");
+            self.claim_cooldown = claim_cooldown;
+        }
+
+        // This method sets the maximum amount of periods rewards stay claimable
+        //
+        // ## INPUT
+        // - `new_delay`: the new maximum claim delay in periods
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - if the delay is reduced, the unclaimed rewards of the periods that are no longer claimable are released from the reserved rewards and moved to the forfeited vault
+        // - if the delay is reduced, an event is emitted recording the forfeited rewards
+        // - released periods are recorded, so raising the delay again never makes them claimable
+        // - the method sets the maximum claim delay
+        pub fn set_max_claim_delay(&mut self, new_delay: i64) {
+            assert!(new_delay > 0, "Maximum claim delay must be positive.");
+
+            if new_delay < self.max_claim_delay {
+                let mut forfeited_rewards: Decimal = dec!(0);
+                for period in (self.released_through + 1)..(self.current_period - new_delay) {
+                    if let Some(unclaimed) = self.unclaimed_period_rewards.remove(&period) {
+                        forfeited_rewards += unclaimed;
+                    }
+                }
+                self.released_through = self.released_through.max(self.current_period - new_delay - 1);
+
+                self.release_reserved_rewards(forfeited_rewards);
+                let forfeited: FungibleBucket = self.reward_vault.take_advanced(
+                    forfeited_rewards.min(self.reward_vault.amount()),
+                    WithdrawStrategy::Rounded(RoundingMode::ToZero),
+                );
+                self.forfeited_vault.put(forfeited);
+
+                Runtime::emit_event(MaxClaimDelayReducedEvent {
+                    old_delay: self.max_claim_delay,
+                    new_delay,
+                    forfeited_rewards,
+                });
+            }
+
+            self.max_claim_delay = new_delay;
+        }
+
+        pub fn collect_forfeited(&mut self) -> FungibleBucket {
+            self.forfeited_vault.take_all()
+        }
+
+        pub fn set_unstake_delay(&mut self, new_delay: i64) {
+            assert!(new_delay <= self.max_unstaking_delay, "Unstaking delay cannot be longer than the maximum unstaking delay.");
+            self.unstake_delay = new_delay;
+        }
+
+        // This method applies the current unstake delay to an outstanding unstake receipt, if that makes it redeemable earlier
+        //
+        // ## INPUT
+        // - `receipt_id`: the local ID of the unstake receipt
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method calculates the redemption time the receipt would get under the current unstake delay, including the stakable's tiered delay over the staking ID's outstanding unstaking amount
+        // - the new redemption time is never earlier than the expiry of the lock the receipt's tokens were queued from
+        // - the method updates the receipt's redemption time if the new one is earlier, it is never postponed
+        pub fn accelerate_unstake(&mut self, receipt_id: NonFungibleLocalId) {
+            let receipt_data: UnstakeReceipt = self
+                .unstake_receipt_manager
+                .get_non_fungible_data(&receipt_id);
+
+            let amount_unstaking: Option<Decimal> = receipt_data.id.as_ref().map(|id| {
+                let id_data: Id = self.id_manager.get_non_fungible_data(id);
+                id_data
+                    .resources
+                    .get(&receipt_data.address)
+                    .map_or(receipt_data.amount, |resource| resource.amount_unstaking)
+            });
+            let mut redemption_time: Instant = Clock::current_time_rounded_to_minutes()
+                .add_days(self.unstake_delay_for(receipt_data.address, amount_unstaking))
+                .unwrap();
+            if let Some(queued_until) = receipt_data.queued_until {
+                if queued_until.seconds_since_unix_epoch > redemption_time.seconds_since_unix_epoch {
+                    redemption_time = queued_until;
+                }
+            }
+
+            if redemption_time.seconds_since_unix_epoch
+                < receipt_data.redemption_time.seconds_since_unix_epoch
+            {
+                self.unstake_receipt_manager.update_non_fungible_data(
+                    &receipt_id,
+                    "redemption_time",
+                    redemption_time,
+                );
+            }
+        }
+
+        // This method updates the name and symbol of the project in the metadata of the component's resources
+        //
+        // ## INPUT
+        // - `name`: the new name of the project
+        // - `symbol`: the new symbol of the project
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method updates the name, symbol and description metadata of the staking ID, unstake receipt, stake transfer receipt and reward vesting receipt
+        // - the method stores the new name and symbol
+        pub fn update_branding(&mut self, name: String, symbol: String) {
+            self.id_manager
+                .set_metadata("name", format!("{} Staking ID", name));
+            self.id_manager
+                .set_metadata("symbol", format!("id{}", symbol));
+            self.id_manager.set_metadata(
+                "description",
+                format!("An ID recording your stake in the {} ecosystem.", name),
+            );
+
+            self.stake_transfer_receipt_manager
+                .set_metadata("name", format!("{} Stake Transfer Receipt", name));
+            self.stake_transfer_receipt_manager
+                .set_metadata("symbol", format!("staketr{}", symbol));
+            self.stake_transfer_receipt_manager.set_metadata(
+                "description",
+                format!("An stake transfer receipt used in the {} ecosystem.", name),
+            );
+
+            self.unstake_receipt_manager
+                .set_metadata("name", format!("{} Unstake Receipt", name));
+            self.unstake_receipt_manager
+                .set_metadata("symbol", format!("unstake{}", symbol));
+            self.unstake_receipt_manager.set_metadata(
+                "description",
+                format!("An unstake receipt used in the {} ecosystem.", name),
+            );
+
+            self.vesting_receipt_manager
+                .set_metadata("name", format!("{} Reward Vesting Receipt", name));
+            self.vesting_receipt_manager
+                .set_metadata("symbol", format!("vest{}", symbol));
+            self.vesting_receipt_manager.set_metadata(
+                "description",
+                format!("A receipt for vesting staking rewards in the {} ecosystem.", name),
+            );
+
+            self.name = name;
+            self.symbol = symbol;
+        }
+
+        pub fn set_vesting_duration(&mut self, vesting_duration: i64) {
+            assert!(vesting_duration >= 0, "Vesting duration cannot be negative.");
+            self.vesting_duration = vesting_duration;
+        }
+
+        pub fn set_paused(&mut self, paused: bool) {
+            self.paused = paused;
+        }
+
+        pub fn set_transfer_whitelist(&mut self, whitelist_badge: Option<ResourceAddress>) {
+            self.transfer_whitelist = whitelist_badge;
+        }
+
+        pub fn set_rewards(&mut self, address: ResourceAddress, reward: Decimal) {
+            let stakable = self.stakes.get_mut(&address).unwrap();
+            if reward < stakable.reward_amount {
+                stakable.last_reward_cut = Some(Clock::current_time_rounded_to_minutes());
+            }
+            stakable.reward_amount = reward;
+        }
+
+        // This method funds the reward vault and sets a stakable's reward in one go
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `funding`: the reward tokens to add to the reward vault
+        // - `reward_amount`: the new reward amount per period of the stakable
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the funding is the reward token
+        // - the method puts the funding in the reward vault
+        // - the method sets the stakable's reward amount
+        pub fn configure_rewards(&mut self, address: ResourceAddress, funding: Bucket, reward_amount: Decimal) {
+            assert!(
+                funding.resource_address() == self.reward_vault.resource_address(),
+                "Funding must be the reward token."
+            );
+            self.reward_vault.put(funding.as_fungible());
+            self.set_rewards(address, reward_amount);
+        }
+
+        pub fn set_unstake_penalty(&mut self, unstake_penalty: Decimal) {
+            assert!(
+                unstake_penalty >= dec!(0) && unstake_penalty <= dec!(1),
+                "Unstake penalty must be between 0 and 1."
+            );
+            self.unstake_penalty = unstake_penalty;
+        }
+
+        pub fn set_penalty_free_window(&mut self, penalty_free_window: i64) {
+            assert!(penalty_free_window >= 0, "Penalty free window cannot be negative.");
+            self.penalty_free_window = penalty_free_window;
+        }
+
+        pub fn set_emergency_penalty(&mut self, emergency_penalty: Option<Decimal>) {
+            if let Some(emergency_penalty) = emergency_penalty {
+                assert!(
+                    emergency_penalty >= dec!(0) && emergency_penalty <= dec!(1),
+                    "Emergency penalty must be between 0 and 1."
+                );
+            }
+            self.emergency_penalty = emergency_penalty;
+        }
+
+        pub fn set_force_unlock_penalty(&mut self, force_unlock_penalty: Option<Decimal>) {
+            if let Some(force_unlock_penalty) = force_unlock_penalty {
+                assert!(
+                    force_unlock_penalty >= dec!(0) && force_unlock_penalty <= dec!(1),
+                    "Force unlock penalty must be between 0 and 1."
+                );
+            }
+            self.force_unlock_penalty = force_unlock_penalty;
+        }
+
+        pub fn set_max_outstanding_unstakes(&mut self, max_outstanding_unstakes: u64) {
+            self.max_outstanding_unstakes = max_outstanding_unstakes;
+        }
+
+        pub fn collect_penalties(&mut self, address: ResourceAddress) -> Bucket {
+            self.stakes
+                .get_mut(&address)
+                .unwrap()
+                .penalty_vault
+                .take_all()
+        }
+
+        // This method sets the window of periods in which a stakable emits rewards, both bounds inclusive
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `start_period`: the first period rewards are recorded for, or none to start immediately
+        // - `end_period`: the last period rewards are recorded for, or none to emit indefinitely
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the window is valid
+        // - the method updates the stakable's reward window
+        pub fn set_reward_window(
+            &mut self,
+            address: ResourceAddress,
+            start_period: Option<i64>,
+            end_period: Option<i64>,
+        ) {
+            if let (Some(start), Some(end)) = (start_period, end_period) {
+                assert!(start <= end, "Reward window must start before it ends.");
+            }
+            let stakable = self.stakes.get_mut(&address).unwrap();
+            stakable.reward_start_period = start_period;
+            stakable.reward_end_period = end_period;
+        }
+
+        pub fn set_min_lock_duration(&mut self, min_lock_duration: i64) {
+            assert!(min_lock_duration > 0, "Minimum lock duration must be positive.");
+            assert!(
+                min_lock_duration <= self.max_lock_duration,
+                "Minimum lock duration cannot be longer than the maximum lock duration."
+            );
+            self.min_lock_duration = min_lock_duration;
+        }
+
+        // This method sets the amount of periods stake has to sit in a staking ID before it earns rewards
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `eligibility_delay`: the amount of periods, counted from the period of the staking ID's latest stake of the token
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method updates the stakable's eligibility delay
+        // - as only the latest stake period is stored, adding stake restarts the delay for the staking ID's full balance of the token
+        pub fn set_eligibility_delay(&mut self, address: ResourceAddress, eligibility_delay: i64) {
+            assert!(eligibility_delay >= 0, "Eligibility delay cannot be negative.");
+            self.stakes.get_mut(&address).unwrap().eligibility_delay = eligibility_delay;
+        }
+
+        pub fn set_emissions_paused(&mut self, address: ResourceAddress, emissions_paused: bool) {
+            self.stakes
+                .get_mut(&address)
+                .expect("Stakable not found.")
+                .emissions_paused = emissions_paused;
+        }
+
+        pub fn set_stakable_active(&mut self, address: ResourceAddress, active: bool) {
+            self.stakes
+                .get_mut(&address)
+                .expect("Stakable not found.")
+                .active = active;
+        }
+
+        pub fn set_require_lock_for_rewards(&mut self, address: ResourceAddress, require_lock_for_rewards: bool) {
+            self.stakes
+                .get_mut(&address)
+                .expect("Stakable not found.")
+                .require_lock_for_rewards = require_lock_for_rewards;
+        }
+
+        pub fn set_tiered_unstake_delay(&mut self, address: ResourceAddress, tiered_unstake_delay: Option<(Decimal, i64)>) {
+            if let Some((threshold, delay)) = tiered_unstake_delay {
+                assert!(threshold > dec!(0), "Tiered unstake threshold must be positive.");
+                assert!(delay >= 0, "Tiered unstake delay cannot be negative.");
+                assert!(delay <= self.max_unstaking_delay, "Tiered unstake delay cannot be longer than the maximum unstaking delay.");
+            }
+            self.stakes
+                .get_mut(&address)
+                .expect("Stakable not found.")
+                .tiered_unstake_delay = tiered_unstake_delay;
+        }
+
+        pub fn set_compound_bonus(&mut self, address: ResourceAddress, compound_bonus: Decimal) {
+            assert!(compound_bonus >= dec!(0), "Compound bonus cannot be negative.");
+            self.stakes
+                .get_mut(&address)
+                .expect("Stakable not found.")
+                .compound_bonus = compound_bonus;
+        }
+
+        pub fn set_emission_curve(&mut self, address: ResourceAddress, emission_curve: EmissionCurve) {
+            self.stakes
+                .get_mut(&address)
+                .expect("Stakable not found.")
+                .emission_curve = emission_curve;
+        }
+
+        pub fn set_reward_budget(&mut self, address: ResourceAddress, reward_budget: Option<Decimal>) {
+            if let Some(reward_budget) = reward_budget {
+                assert!(reward_budget >= dec!(0), "Reward budget cannot be negative.");
+            }
+            self.stakes
+                .get_mut(&address)
+                .expect("Stakable not found.")
+                .reward_budget = reward_budget;
+        }
+
+        // This method moves reward budget from one stakable to another, both funded by the reward vault
+        //
+        // ## INPUT
+        // - `from`: the address of the stakable token to take the budget from
+        // - `to`: the address of the stakable token to give the budget to
+        // - `amount`: the amount of reward budget to move
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the stakables differ, the amount is positive and both stakables have a reward budget
+        // - the method checks the amount does not exceed the unspent budget of the stakable it is taken from
+        // - the method moves the amount between the budgets, the reward vault itself is shared, so no tokens move
+        pub fn rebalance_rewards(&mut self, from: ResourceAddress, to: ResourceAddress, amount: Decimal) {
+            assert!(from != to, "Cannot rebalance rewards to the same stakable.");
+            assert!(amount > dec!(0), "Amount must be positive.");
+
+            let from_budget: Decimal = self
+                .stakes
+                .get(&from)
+                .expect("Stakable not found.")
+                .reward_budget
+                .expect("Both stakables must have a reward budget.");
+            let to_budget: Decimal = self
+                .stakes
+                .get(&to)
+                .expect("Stakable not found.")
+                .reward_budget
+                .expect("Both stakables must have a reward budget.");
+            assert!(amount <= from_budget, "Amount exceeds the unspent reward budget of the stakable.");
+
+            self.stakes.get_mut(&from).unwrap().reward_budget = Some(from_budget - amount);
+            self.stakes.get_mut(&to).unwrap().reward_budget = Some(to_budget + amount);
+        }
+
+        pub fn add_stakable(&mut self, address: ResourceAddress, reward_amount: Decimal, lock: Lock) {
+            assert!(
+                !self.stakes.contains_key(&address),
+                "Stakable already added."
+            );
+            self.validate_stakable_config(reward_amount, &lock);
+            self.stakes.insert(
+                address,
+                StakableUnit {
+                    address,
+                    amount_staked: dec!(0),
+                    vault: Vault::new(address),
+                    reward_amount,
+                    lock,
+                    rewards: KeyValueStore::new(),
+                    reward_start_period: None,
+                    reward_end_period: None,
+                    last_reward_cut: None,
+                    penalty_vault: Vault::new(address),
+                    eligibility_delay: 0,
+                    unstaking_amount: dec!(0),
+                    reward_budget: None,
+                    airdrop_schedule: KeyValueStore::new(),
+                    airdrop_rates: KeyValueStore::new(),
+                    emissions_paused: false,
+                    active: true,
+                    emission_curve: EmissionCurve::Flat,
+                    compound_bonus: dec!(0),
+                    locked_amount: dec!(0),
+                    locked_weight: dec!(0),
+                    lock_expiries: KeyValueStore::new(),
+                    dao_locked_amount: dec!(0),
+                    dao_lock_expiries: KeyValueStore::new(),
+                    require_lock_for_rewards: false,
+                    tiered_unstake_delay: None,
+                },
+            );
+        }
+
+        pub fn edit_stakable(&mut self, address: ResourceAddress, reward_amount: Decimal, lock: Lock) {
+            self.validate_stakable_config(reward_amount, &lock);
+
+            Runtime::emit_event(StakableEditedEvent {
+                address,
+                reward_amount,
+                lock_payment: lock.payment,
+                lock_duration: lock.duration,
+            });
+
+            let stakable = self.stakes.get_mut(&address).unwrap();
+            if reward_amount < stakable.reward_amount {
+                stakable.last_reward_cut = Some(Clock::current_time_rounded_to_minutes());
+            }
+            stakable.reward_amount = reward_amount;
+            stakable.lock = lock;
+        }
+
+        pub fn set_max_catchup_periods(&mut self, max_catchup_periods: i64) {
+            assert!(max_catchup_periods > 0, "Maximum catch-up periods must be positive.");
+            self.max_catchup_periods = max_catchup_periods;
+        }
+
+        // This method rolls a number of overdue periods, for components that have been behind for a long time
+        //
+        // ## INPUT
+        // - `count`: the maximum amount of periods to roll
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks the count is positive
+        // - for at most `count` periods that have already ended, the method records the period's rewards and moves the period start and next period forward one interval
+        // - periods that have not ended yet are never rolled
+        pub fn catch_up_periods(&mut self, count: i64) {
+            assert!(count > 0, "Count must be positive.");
+
+            for _ in 0..count {
+                if !Clock::current_time_is_at_or_after(self.next_period, TimePrecision::Minute) {
+                    break;
+                }
+
+                self.record_period_rewards();
+                self.period_start = self.next_period;
+                self.next_period = self.next_period.add_days(self.period_interval).unwrap();
+            }
+        }
+
+        // This method ends the current period immediately
+        //
+        // ## INPUT
+        // - none
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method first rolls any period that has already passed, so its rewards are recorded normally, and checks the component has fully caught up
+        // - the method checks the current period has been running for at least a minute, so periods cannot be rolled instantly one after another
+        // - the method sets the next period to now and rolls it, recording the truncated period's rewards as if it were a full period
+        pub fn set_next_period_to_now(&mut self) {
+            self.roll_period();
+            assert!(
+                !Clock::current_time_is_at_or_after(self.next_period, TimePrecision::Minute),
+                "Component is behind, call update_period until it has caught up."
+            );
+
+            let now: Instant = Clock::current_time_rounded_to_minutes();
+            assert!(
+                now.seconds_since_unix_epoch > self.period_start.seconds_since_unix_epoch,
+                "Current period has only just started."
+            );
+
+            self.next_period = now;
+            self.roll_period();
+        }
+
+        // This method locks staked tokens for voting
+        //
+        // ## INPUT
+        // - `address`: the address of the stakable token
+        // - `lock_until`: the date until which the tokens are locked
+        // - `id`: the staking ID
+        //
+        // ## OUTPUT
+        // - none
+        //
+        // ## LOGIC
+        // - the method checks whether a DAO is controlling the staking
+        // - the method checks whether the token is stakable and staked by the staking ID
+        // - the method checks the lock ends in the future, but no later than the maximum lock duration from now
+        // - the method updates the locked_until field of the staking ID appropriately, recording the end of the DAO lock, before which the tokens cannot be force unlocked
+        // - an existing lock or DAO lock ending later is kept, so a lock is only ever extended by this method
+        // - the lock payment of an ended lock is cleared, as there is nothing left to refund
+        // - a DAO lock only prevents unstaking, it does not weight the staking ID's rewards
+        // - the DAO locked tokens count towards the stakable's locked total until the end of the last period the DAO lock fully covers (see `count_dao_lock`)
+        
+        pub fn set_lock(&mut self, address: ResourceAddress, lock_until: Instant, id: NonFungibleLocalId) {
+            assert!(self.dao_controlled, "This functionality is only available if a DAO is controlling the staking.");
+            assert!(self.stakes.get(&address).is_some(), "Stakable not found.");
+
+            let now: Instant = Clock::current_time_rounded_to_minutes();
+            let max_lock_until: Instant = now.add_days(self.max_lock_duration).unwrap();
+            assert!(
+                lock_until.seconds_since_unix_epoch > now.seconds_since_unix_epoch,
+                "Lock must end in the future."
+            );
+            assert!(
+                lock_until.seconds_since_unix_epoch <= max_lock_until.seconds_since_unix_epoch,
+                "Lock exceeds the maximum lock duration."
+            );
+
+            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+
+            let mut resource_map = id_data.resources.clone();
+            let mut resource = resource_map
+                .get(&address)
+                .expect("Stakable not found in staking ID.")
+                .clone();
+               
+            if resource.locked_amount() == dec!(0) {
+                resource.lock_payment = dec!(0);
+            }
+            self.count_dao_lock(address, &resource, false);
+            resource.locked_until = match resource.locked_until {
+                Some(locked_until)
+                    if locked_until.seconds_since_unix_epoch > lock_until.seconds_since_unix_epoch =>
+                {
+                    Some(locked_until)
+                }
+                _ => Some(lock_until),
+            };
+            resource.amount_locked = resource.amount_staked;
+            resource.dao_locked_until = match resource.dao_locked_until {
+                Some(dao_locked_until)
+                    if dao_locked_until.seconds_since_unix_epoch > lock_until.seconds_since_unix_epoch =>
+                {
+                    Some(dao_locked_until)
+                }
+                _ => Some(lock_until),
+            };
+            resource.dao_counted = resource.amount_staked;
+            resource.dao_lock_end_period = self.lock_end_period(resource.dao_locked_until.unwrap());
+            self.count_dao_lock(address, &resource, true);
+            resource_map.insert(address, resource);
+
+            self.id_manager
+                .update_non_fungible_data(&id, "resources", resource_map);
+        }
+
+        //////////////////////////////////////////////////////////////////////
+        ////////////////////////////HELPER METHODS////////////////////////////
+        //////////////////////////////////////////////////////////////////////
+
+        /// This function combines the component's global caller rule with the owner rule.
+        /// 
+        /// ## INPUT
+        /// - `component_address`: the address of the staking component
+        /// - `owner_rule`: the owner rule of the staking component
+        ///
+        /// ## OUTPUT
+        /// - a rule satisfied by the staking component or by whoever satisfies the owner rule
+        /// 
+        /// ## LOGIC
+        /// - if the owner rule allows anyone, anyone is allowed
+        /// - if the owner rule denies everyone, only the staking component is allowed
+        /// - otherwise, the component's global caller rule and the owner rule are combined, either one satisfying the result
+
+        fn component_or_owner_rule(component_address: ComponentAddress, owner_rule: &AccessRule) -> AccessRule {
+            let component_node: AccessRuleNode =
+                AccessRuleNode::ProofRule(require(global_caller(component_address)));
+
+            match owner_rule {
+                AccessRule::AllowAll => AccessRule::AllowAll,
+                AccessRule::DenyAll => AccessRule::Protected(component_node),
+                AccessRule::Protected(owner_node) => AccessRule::Protected(AccessRuleNode::AnyOf(
+                    vec![component_node, owner_node.clone()],
+                )),
+            }
+        }
+
+        /// This method updates the component's period and saves the rewards accompanying the period.
+        /// 
+        /// ## INPUT
+        /// - none
+        ///
+        /// ## OUTPUT
+        /// - whether a period was rolled
+        /// 
+        /// ## LOGIC
+        /// - if the next period has not started yet, the method returns without changing any state
+        /// - the method calculates the number of extra periods that have passed since the last update, because the method might not be called exactly at the end of a period
+        /// - the rewards of the ended period and of every extra period are recorded, so skipped periods remain claimable
+        /// - at most `max_catchup_periods` periods are rolled, so a long stale component needs repeated calls to catch up
+        /// - the start of the new current period is stored and the next period is set one interval after it
+        /// - no reentrancy guard is needed: the method calls no other component, and the engine write-locks the component's state for the whole call, so a reentrant call into the component fails instead of rolling the period twice
+
+        fn roll_period(&mut self) -> bool {
+            if !Clock::current_time_is_at_or_after(self.next_period, TimePrecision::Minute) {
+                return false;
+            }
+
+            let extra_periods_dec: Decimal = ((Clock::current_time_rounded_to_minutes()
+                .seconds_since_unix_epoch
+                - self.next_period.seconds_since_unix_epoch)
+                / (Decimal::from(self.period_interval) * dec!(86400)))
+            .checked_floor()
+            .unwrap();
+
+            let extra_periods: i64 = i64::try_from(extra_periods_dec.0 / Decimal::ONE.0)
+                .unwrap()
+                .min(self.max_catchup_periods - 1);
+
+            for _ in 0..(1 + extra_periods) {
+                self.record_period_rewards();
+            }
+
+            self.period_start = self
+                .next_period
+                .add_days(extra_periods * self.period_interval)
+                .unwrap();
+            self.next_period = self.period_start.add_days(self.period_interval).unwrap();
+
+            true
+        }
+
+        /// This method records the rewards of the current period and increments the current period.
+        /// 
+        /// ## INPUT
+        /// - none
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - for each stakable token the rewards are calculated and recorded, reward calculation is relatively simple:
+        ///    - every stakable has a total amount of reward per period, following its emission curve and multiplied by the global reward multiplier
+        ///    - total reward amount is divided by the weighted total amount staked, locked tokens weighted by their lock multiplier and unlocked tokens left out if rewards require a lock, to get the reward per weighted staked token
+        ///    - if the period lies outside the stakable's reward window or its emissions are paused, a reward of zero is recorded
+        ///    - if the stakable has a reward budget, the total reward amount is capped to it and deducted from it
+        ///    - if rounding down rewards is enabled, the reward per staked token is rounded down and the remainder is counted as dust
+        ///    - the reward is recorded per unlocked token and per weighted locked token, the former being zero if rewards require a lock, so changing this requirement never affects recorded periods
+        /// - for each stakable token with an airdrop scheduled for the period, the airdrop per staked token is recorded, unless nothing is staked, deallocating the airdropped tokens so they can be recovered through `remove_airdrop_tokens`
+        /// - the locks and DAO locks whose last period is the recorded period are removed from each stakable's locked totals
+        /// - the total staked amount of each stakable token is recorded in the staked history
+        /// - the recorded rewards are added to the reserved rewards, as they are now owed to stakers, and recorded as the period's unclaimed rewards
+        /// - the dust is moved from the reward vault to the dust vault, as far as the reward vault holds unreserved rewards
+        /// - the current period is incremented
+        /// - the unclaimed rewards of the period that just became too old to claim are released from the reserved rewards, making them available again, and the period is recorded as released
+
+        fn record_period_rewards(&mut self) {
+            let current_period = self.current_period;
+            let round_down_rewards = self.round_down_rewards;
+            let global_reward_multiplier = self.global_reward_multiplier;
+            let mut recorded_rewards: Decimal = dec!(0);
+            let mut dust: Decimal = dec!(0);
+            let mut unallocated_airdrops: Vec<(ResourceAddress, Decimal)> = vec![];
+
+            for (address, stakable_unit) in self.stakes.iter_mut() {
+                self.staked_history
+                    .insert((*address, current_period), stakable_unit.amount_staked);
+
+                let emission: Decimal =
+                    stakable_unit.budgeted_emission(current_period, global_reward_multiplier);
+                if let Some(reward_budget) = stakable_unit.reward_budget.as_mut() {
+                    *reward_budget -= emission;
+                }
+
+                if emission > dec!(0) {
+                    let reward_base: Decimal = stakable_unit.reward_base();
+                    let mut reward_per_token: Decimal = emission / reward_base;
+                    if round_down_rewards {
+                        reward_per_token = reward_per_token
+                            .checked_round(REWARD_RATE_DECIMALS, RoundingMode::ToZero)
+                            .unwrap();
+                        dust += emission - reward_per_token * reward_base;
+                    }
+                    let unlocked_reward_per_token: Decimal = if stakable_unit.require_lock_for_rewards {
+                        dec!(0)
+                    } else {
+                        reward_per_token
+                    };
+                    stakable_unit
+                        .rewards
+                        .insert(current_period, (unlocked_reward_per_token, reward_per_token));
+                    recorded_rewards += reward_per_token * reward_base;
+                } else {
+                    stakable_unit.rewards.insert(current_period, (dec!(0), dec!(0)));
+                }
+
+                let airdrop: Option<(ResourceAddress, Decimal)> = stakable_unit
+                    .airdrop_schedule
+                    .get(&current_period)
+                    .map(|airdrop| *airdrop);
+                if let Some((resource, amount)) = airdrop {
+                    if stakable_unit.amount_staked > dec!(0) {
+                        stakable_unit.airdrop_rates.insert(
+                            current_period,
+                            (resource, amount / stakable_unit.amount_staked),
+                        );
+                    } else {
+                        unallocated_airdrops.push((resource, amount));
+                    }
+                }
+
+                if let Some((amount, weight)) = stakable_unit.lock_expiries.remove(&current_period) {
+                    stakable_unit.locked_amount -= amount;
+                    stakable_unit.locked_weight -= weight;
+                }
+                if let Some(amount) = stakable_unit.dao_lock_expiries.remove(&current_period) {
+                    stakable_unit.dao_locked_amount -= amount;
+                }
+            }
+
+            for (resource, amount) in unallocated_airdrops {
+                self.deallocate_airdrop(resource, amount);
+            }
+
+            self.reserved_rewards += recorded_rewards;
+            self.unclaimed_period_rewards.insert(current_period, recorded_rewards);
+
+            let unreserved: Decimal = self.reward_vault.amount() - self.reserved_rewards;
+            let dust: Decimal = dust.min(unreserved);
+            if dust > dec!(0) {
+                let dust_bucket: FungibleBucket = self
+                    .reward_vault
+                    .take_advanced(dust, WithdrawStrategy::Rounded(RoundingMode::ToZero));
+                self.dust_vault.put(dust_bucket);
+            }
+
+            self.current_period += 1;
 
-            let mut claimed_weeks: i64 = self.current_period - id_data.next_period + 1;
-            if claimed_weeks > self.max_claim_delay {
-                claimed_weeks = self.max_claim_delay;
+            let expired_period: i64 = self.current_period - self.max_claim_delay - 1;
+            if let Some(unclaimed) = self.unclaimed_period_rewards.remove(&expired_period) {
+                self.release_reserved_rewards(unclaimed);
+            }
+            self.released_through = self.released_through.max(expired_period);
+        }
+
+        /// This method claims the rewards of a staking ID.
+        /// 
+        /// ## INPUT
+        /// - `id`: the staking ID
+        /// - `max_periods`: an optional maximum amount of periods to claim
+        ///
+        /// ## OUTPUT
+        /// - the claimed reward amount, or none if there are no periods to claim
+        /// 
+        /// ## LOGIC
+        /// - the method calculates the rewards of the unclaimed periods (see `calculate_rewards`), returning none if there are none
+        /// - the method checks the claim cooldown has passed since the staking ID's last claim and records the time of this claim
+        /// - the method updates the staking ID's next period to just after the last claimed period, so no period is claimed twice or skipped
+        /// - the method records the reward claimed for each period in the claim history and deducts it from the period's unclaimed rewards, failing if it exceeds them
+        /// - the method releases each period's claimed rewards from the reserved rewards
+        /// - if a maximum lifetime reward is set, the claimed rewards are capped so the staking ID's total claimed rewards don't exceed it, the rest stays in the reward vault
+        /// - airdrops recorded for the claimed periods are added to the staking ID, to be paid out by `pay_airdrops`
+
+        fn claim_rewards(&mut self, id: &NonFungibleLocalId, max_periods: Option<i64>) -> Option<Decimal> {
+            let id_data: Id = self.id_manager.get_non_fungible_data(id);
+
+            let (first_period, period_rewards, airdrops) =
+                self.calculate_rewards(&id_data, max_periods)?;
+
+            assert!(
+                self.claim_cooldown_passed(&id_data),
+                "Claim cooldown has not passed yet."
+            );
+            self.id_manager.update_non_fungible_data(
+                id,
+                "last_claim",
+                Some(Clock::current_time_rounded_to_minutes()),
+            );
+
+            let last_period: i64 = first_period + period_rewards.len() as i64 - 1;
+            self.id_manager
+                .update_non_fungible_data(id, "next_period", last_period + 2);
+
+            let mut staking_reward: Decimal = dec!(0);
+
+            for (period, period_reward) in (first_period..).zip(period_rewards) {
+                self.claimed_rewards.insert((id.clone(), period), period_reward);
+                let unclaimed: Decimal = self
+                    .unclaimed_period_rewards
+                    .get(&period)
+                    .map_or(dec!(0), |unclaimed| *unclaimed);
+                assert!(
+                    period_reward <= unclaimed,
+                    "Claimed rewards exceed the unclaimed rewards of the period."
+                );
+                self.unclaimed_period_rewards.insert(period, unclaimed - period_reward);
+                self.release_reserved_rewards(period_reward);
+                staking_reward += period_reward;
+            }
+
+            let staking_reward: Decimal = self.cap_lifetime_reward(&id_data, staking_reward);
+            self.id_manager.update_non_fungible_data(
+                id,
+                "total_claimed",
+                id_data.total_claimed + staking_reward,
+            );
+
+            self.id_manager
+                .update_non_fungible_data(id, "airdrops", airdrops);
+
+            Some(staking_reward)
+        }
+
+        /// This method calculates the rewards a staking ID can claim, without changing any state.
+        /// 
+        /// ## INPUT
+        /// - `id_data`: the data of the staking ID
+        /// - `max_periods`: an optional maximum amount of periods to calculate
+        ///
+        /// ## OUTPUT
+        /// - the first claimable period, or none if there are no periods to claim
+        /// - the reward of every claimable period, oldest first
+        /// - the staking ID's airdrops, including those recorded for the claimable periods
+        /// 
+        /// ## LOGIC
+        /// - the method determines the unclaimed periods, limited to the maximum claim delay and to periods whose rewards have not been released, at most `max_periods` of them oldest first
+        /// - the unclaimed periods are `next_period - 1` up to and including `current_period - 1`, the last fully completed period
+        /// - the method iterates over all claimable periods and staked tokens and calculates the rewards
+        /// - periods before a token's last stake period plus its eligibility delay earn no rewards for that token
+        /// - unlocked tokens earn the period's reward per unlocked token, locked tokens the reward per weighted locked token times their lock's multiplier, for the periods their lock covers
+        /// - if a stakable's rewards required a lock when the period was recorded, its reward per unlocked token is zero, so only the staking ID's locked tokens earn rewards
+        /// - airdrops recorded for the claimable periods are added to the staking ID's airdrops
+
+        fn calculate_rewards(
+            &self,
+            id_data: &Id,
+            max_periods: Option<i64>,
+        ) -> Option<(i64, Vec<Decimal>, HashMap<ResourceAddress, Decimal>)> {
+            let first_period: i64 =
+                (id_data.next_period - 1)
+                    .max(self.current_period - self.max_claim_delay)
+                    .max(self.released_through + 1);
+            let mut last_period: i64 = self.current_period - 1;
+            if let Some(max_periods) = max_periods {
+                last_period = last_period.min(first_period + max_periods - 1);
+            }
+
+            if last_period < first_period {
+                return None;
+            }
+
+            let mut period_rewards: Vec<Decimal> = vec![];
+            let mut airdrops: HashMap<ResourceAddress, Decimal> = id_data.airdrops.clone();
+
+            for period in first_period..(last_period + 1) {
+                let mut period_reward: Decimal = dec!(0);
+
+                for (address, stakable_unit) in self.stakes.iter() {
+                    let resource = match id_data.resources.get(address) {
+                        Some(resource) => resource,
+                        None => continue,
+                    };
+
+                    if period < resource.last_stake_period + stakable_unit.eligibility_delay {
+                        continue;
+                    }
+
+                    if let Some(reward) = stakable_unit.rewards.get(&period) {
+                        let (unlocked, locked_weight) = resource.reward_weights(period);
+                        period_reward += reward.0 * unlocked + reward.1 * locked_weight;
+                    }
+
+                    if let Some(airdrop) = stakable_unit.airdrop_rates.get(&period) {
+                        *airdrops.entry(airdrop.0).or_insert(dec!(0)) +=
+                            airdrop.1 * resource.amount_staked;
+                    }
+                }
+
+                period_rewards.push(period_reward);
+            }
+
+            Some((first_period, period_rewards, airdrops))
+        }
+
+        /// This method checks whether the claim cooldown of a staking ID has passed.
+        /// 
+        /// ## INPUT
+        /// - `id_data`: the data of the staking ID
+        ///
+        /// ## OUTPUT
+        /// - whether the staking ID can claim again
+        /// 
+        /// ## LOGIC
+        /// - a staking ID that never claimed can always claim
+        /// - otherwise, the claim cooldown has to have passed since the staking ID's last claim
+
+        fn claim_cooldown_passed(&self, id_data: &Id) -> bool {
+            id_data.last_claim.map_or(true, |last_claim| {
+                Clock::current_time_is_at_or_after(
+                    last_claim.add_minutes(self.claim_cooldown).unwrap(),
+                    TimePrecision::Minute,
+                )
+            })
+        }
+
+        /// This method caps a staking ID's rewards to what it can still claim under the maximum lifetime reward.
+        /// 
+        /// ## INPUT
+        /// - `id_data`: the data of the staking ID
+        /// - `reward`: the staking ID's rewards
+        ///
+        /// ## OUTPUT
+        /// - the rewards, capped by the maximum lifetime reward if one is set
+        /// 
+        /// ## LOGIC
+        /// - if a maximum lifetime reward is set, the rewards are capped so the staking ID's total claimed rewards don't exceed it
+        /// - otherwise, the rewards are returned unchanged
+
+        fn cap_lifetime_reward(&self, id_data: &Id, reward: Decimal) -> Decimal {
+            match self.max_lifetime_reward {
+                Some(max_lifetime_reward) => {
+                    reward.min((max_lifetime_reward - id_data.total_claimed).max(dec!(0)))
+                }
+                None => reward,
+            }
+        }
+
+        /// This method pays out the airdrops claimed by a staking ID.
+        /// 
+        /// ## INPUT
+        /// - `id`: the staking ID
+        ///
+        /// ## OUTPUT
+        /// - a bucket of every airdropped token claimed by the staking ID
+        /// 
+        /// ## LOGIC
+        /// - the method takes each claimed airdrop from the airdrop vault of its token, rounded down to the token's divisibility
+        /// - the method deallocates each claimed airdrop, the rounded down remainder becoming removable
+        /// - the method clears the staking ID's claimed airdrops
+
+        fn pay_airdrops(&mut self, id: &NonFungibleLocalId) -> Vec<Bucket> {
+            let id_data: Id = self.id_manager.get_non_fungible_data(id);
+
+            let mut airdrops: Vec<Bucket> = vec![];
+            for (resource, amount) in id_data.airdrops.iter() {
+                airdrops.push(
+                    self.airdrop_vaults
+                        .get_mut(resource)
+                        .unwrap()
+                        .take_advanced(*amount, WithdrawStrategy::Rounded(RoundingMode::ToZero)),
+                );
+                self.deallocate_airdrop(*resource, *amount);
+            }
+
+            self.id_manager
+                .update_non_fungible_data(id, "airdrops", HashMap::<ResourceAddress, Decimal>::new());
+
+            airdrops
+        }
+
+        /// This method removes airdropped tokens from the tokens allocated to stakers.
+        /// 
+        /// ## INPUT
+        /// - `resource`: the airdropped token
+        /// - `amount`: the amount of airdropped tokens no longer allocated
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method subtracts the amount from the token's allocated airdrops, never going below zero
+
+        fn deallocate_airdrop(&mut self, resource: ResourceAddress, amount: Decimal) {
+            let allocated: Decimal = self.allocated_airdrops.get(&resource).map_or(dec!(0), |allocated| *allocated);
+            self.allocated_airdrops
+                .insert(resource, (allocated - amount).max(dec!(0)));
+        }
+
+        /// This method releases rewards that are no longer owed to stakers from the reserved rewards.
+        /// 
+        /// ## INPUT
+        /// - `amount`: the amount of rewards no longer owed
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method checks the amount does not exceed the reserved rewards, as that would mean rewards were paid that were never recorded
+        /// - the method subtracts the amount from the reserved rewards
+
+        fn release_reserved_rewards(&mut self, amount: Decimal) {
+            assert!(
+                amount <= self.reserved_rewards,
+                "Released rewards exceed the reserved rewards."
+            );
+            self.reserved_rewards -= amount;
+        }
+
+        /// This method pays out claimed rewards.
+        /// 
+        /// ## INPUT
+        /// - `amount`: the amount of claimed rewards
+        ///
+        /// ## OUTPUT
+        /// - the claimed rewards, or a vesting receipt for them if vesting is enabled
+        /// 
+        /// ## LOGIC
+        /// - the method takes the rewards from the reward vault and adds them to the total rewards distributed
+        /// - if vesting is disabled, the method returns the rewards
+        /// - if vesting is enabled, the method puts the rewards in the vesting vault and mints a vesting receipt for them
+
+        fn pay_rewards(&mut self, amount: Decimal) -> Bucket {
+            let rewards: FungibleBucket = self.reward_vault.take(amount);
+            self.total_rewards_distributed += amount;
+
+            if self.vesting_duration == 0 {
+                return rewards.into();
+            }
+
+            let vest_start: Instant = Clock::current_time_rounded_to_minutes();
+            let vesting_receipt = RewardVestingReceipt {
+                amount,
+                vest_start,
+                vest_end: vest_start.add_days(self.vesting_duration).unwrap(),
+                amount_claimed: dec!(0),
+            };
+
+            self.vesting_vault.put(rewards);
+            self.vesting_receipt_counter = self
+                .vesting_receipt_counter
+                .checked_add(1)
+                .expect("Vesting receipt counter overflow.");
+            self.vesting_receipt_manager.mint_non_fungible(
+                &NonFungibleLocalId::integer(self.vesting_receipt_counter),
+                vesting_receipt,
+            )
+        }
+
+        /// This method checks the configuration of a stakable.
+        /// 
+        /// ## INPUT
+        /// - `reward_amount`: the reward amount per period of the stakable
+        /// - `lock`: the locking options of the stakable
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method checks the reward amount and lock payment are not negative and the lock multiplier is at least 1
+        /// - a lock duration of 0 disables locking, in which case the method checks the lock has no payment and no multiplier
+        /// - otherwise, the method checks the lock duration lies between the minimum and maximum lock duration
+
+        fn validate_stakable_config(&self, reward_amount: Decimal, lock: &Lock) {
+            assert!(reward_amount >= dec!(0), "Reward amount cannot be negative.");
+            assert!(lock.payment >= dec!(0), "Lock payment cannot be negative.");
+            assert!(lock.multiplier >= dec!(1), "Lock multiplier cannot be less than 1.");
+            assert!(lock.duration >= 0, "Lock duration cannot be negative.");
+            if lock.duration == 0 {
+                assert!(
+                    lock.payment == dec!(0) && lock.multiplier == dec!(1),
+                    "A stakable that is not lockable cannot have a lock payment or multiplier."
+                );
+            } else {
+                assert!(lock.duration >= self.min_lock_duration, "Lock duration is shorter than the minimum lock duration.");
+                assert!(lock.duration <= self.max_lock_duration, "Lock duration is longer than the maximum lock duration.");
             }
+        }
 
-            assert!(claimed_weeks > 0, "Wait longer to claim your rewards.");
+        /// This method returns the unstake delay applying to an unstake of a stakable.
+        /// 
+        /// ## INPUT
+        /// - `address`: the address of the stakable token
+        /// - `amount_unstaking`: the amount of tokens the staking ID has unstaking of the stakable, including this unstake, or none if the unstake is not tied to a staking ID
+        ///
+        /// ## OUTPUT
+        /// - the unstake delay in days
+        /// 
+        /// ## LOGIC
+        /// - if the stakable has a tiered unstake delay and the staking ID's unstaking amount exceeds its threshold, the longer of the tiered delay and the unstake delay applies
+        /// - the whole unstaking amount counts, so splitting a withdrawal into smaller unstakes does not avoid the tiered delay
+        /// - unstakes not tied to a staking ID, converted from stake transfer receipts, always get the tiered delay, as splitting them cannot be tracked
+        /// - otherwise, the unstake delay applies
+
+        fn unstake_delay_for(&self, address: ResourceAddress, amount_unstaking: Option<Decimal>) -> i64 {
+            match self.stakes.get(&address).unwrap().tiered_unstake_delay {
+                Some((threshold, delay)) if amount_unstaking.map_or(true, |amount| amount > threshold) => {
+                    delay.max(self.unstake_delay)
+                }
+                _ => self.unstake_delay,
+            }
+        }
 
-            let mut staking_reward: Decimal = dec!(0);
+        /// This method returns the unstake penalty currently applying to a stakable.
+        /// 
+        /// ## INPUT
+        /// - `address`: the address of the stakable token
+        ///
+        /// ## OUTPUT
+        /// - the fraction of unstaked tokens charged as a penalty
+        /// 
+        /// ## LOGIC
+        /// - if the stakable's reward was cut less than the penalty free window ago, no penalty applies
+        /// - otherwise, the unstake penalty applies
 
-            self.id_manager
-                .update_non_fungible_data(&id, "next_period", self.current_period + 1);
+        fn unstake_penalty_for(&self, address: ResourceAddress) -> Decimal {
+            let last_reward_cut: Option<Instant> = self.stakes.get(&address).unwrap().last_reward_cut;
 
-            for (address, stakable_unit) in self.stakes.iter() {
-                for week in 1..(claimed_weeks + 1) {
-                    if stakable_unit
-                        .rewards
-                        .get(&(self.current_period - week))
-                        .is_some()
-                    {
-                        staking_reward += *stakable_unit
-                            .rewards
-                            .get(&(self.current_period - week))
-                            .unwrap()
-                            * id_data
-                                .resources
-                                .get(&address)
-                                .map_or(dec!(0), |resource| resource.amount_staked);
-                    }
+            if let Some(last_reward_cut) = last_reward_cut {
+                let window_end: Instant = last_reward_cut.add_days(self.penalty_free_window).unwrap();
+                if !Clock::current_time_is_at_or_after(window_end, TimePrecision::Minute) {
+                    return dec!(0);
                 }
             }
 
-            self.reward_vault.take(staking_reward)
+            self.unstake_penalty
         }
 
-        // This method locks staked tokens for a certain duration and gives rewards for locking them
-        //
-        // ## INPUT
-        // - `address`: the address of the stakable token
-        // - `id_proof`: the proof of the staking ID
-        //
-        // ## OUTPUT
-        // - rewards for locking the tokens
-        //
-        // ## LOGIC
-        // - the method checks the staking ID
-        // - the method checks whether this resource address is lockable
-        // - the method checks whether the staking ID tokens are already locked
-        // - the method locks the tokens by updating the staking ID
-        // - the method returns the rewards for locking the tokens
+        /// This method moves a penalty out of a stakable's vault.
+        /// 
+        /// ## INPUT
+        /// - `address`: the address of the stakable token
+        /// - `amount`: the amount of tokens charged as a penalty
+        ///
+        /// ## OUTPUT
+        /// - the penalty actually charged
+        /// 
+        /// ## LOGIC
+        /// - the method rounds the penalty down to the token's divisibility, so the vault can always withdraw it
+        /// - the method takes the penalty from the stakable's vault
+        /// - if the stakable token is the reward token, the penalty is added to the reward vault
+        /// - otherwise, the penalty is added to the stakable's penalty vault, to be collected by the admin
+
+        fn collect_penalty(&mut self, address: ResourceAddress, amount: Decimal) -> Decimal {
+            let divisibility: u8 = ResourceManager::from(address)
+                .resource_type()
+                .divisibility()
+                .unwrap_or(0);
+            let amount: Decimal = amount
+                .checked_round(divisibility as i32, RoundingMode::ToZero)
+                .unwrap();
+            if amount == dec!(0) {
+                return amount;
+            }
 
+            let stakable = self.stakes.get_mut(&address).unwrap();
+            let penalty: Bucket = stakable.vault.take(amount);
 
-        pub fn lock_stake(&mut self, address: ResourceAddress, id_proof: NonFungibleProof) -> FungibleBucket {
-            let id_proof =
-                id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
-            let id = id_proof.non_fungible::<Id>().local_id().clone();
-            let stakable = self.stakes.get(&address).unwrap();
+            if address == self.reward_vault.resource_address() {
+                self.reward_vault.put(penalty.as_fungible());
+            } else {
+                stakable.penalty_vault.put(penalty);
+            }
 
-            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
-            let mut resource_map = id_data.resources.clone();
-            let mut resource = resource_map
-                .get(&address)
-                .expect("Stakable not found in staking ID.")
-                .clone();
+            amount
+        }
 
-            let amount_staked = resource.amount_staked;
-       
-            if let Some(locked_until) = resource.locked_until {
-                assert!(Clock::current_time_is_at_or_after(locked_until, TimePrecision::Minute), "Tokens are already locked.");
+        /// This method determines the staking ID to stake to.
+        /// 
+        /// ## INPUT
+        /// - `id_proof`: an optional proof of the staking ID
+        ///
+        /// ## OUTPUT
+        /// - the staking ID
+        /// - the new staking ID, if none was supplied
+        /// 
+        /// ## LOGIC
+        /// - if a proof is supplied, the method checks it and returns its staking ID
+        /// - otherwise, the method creates a new staking ID
+
+        fn id_or_new(&mut self, id_proof: Option<Proof>) -> (NonFungibleLocalId, Option<Bucket>) {
+            if let Some(id_proof) = id_proof {
+                let id_proof =
+                    id_proof.check_with_message(self.id_manager.address(), "Invalid Id supplied!");
+                let id = id_proof.as_non_fungible().non_fungible::<Id>().local_id().clone();
+                (id, None)
+            } else {
+                let new_id = self.create_id();
+                let id = new_id.as_non_fungible().non_fungible::<Id>().local_id().clone();
+                (id, Some(new_id))
             }
+        }
 
-            let lock_until: Instant = Clock::current_time_rounded_to_minutes().add_days(stakable.lock.duration).unwrap();                 
-            resource.locked_until = Some(lock_until);
-            resource_map.insert(address, resource);
+        /// This method stakes tokens or a stake transfer receipt to a staking ID.
+        /// 
+        /// ## INPUT
+        /// - `id`: the staking ID
+        /// - `stake_bucket`: the staked tokens or a stake transfer receipt
+        /// - `whitelist_proof`: an optional proof of the whitelist badge, required to redeem a stake transfer receipt if a whitelist is set
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method checks if latest rewards have been claimed, if not, the method fails
+        /// - the method checks whether it received tokens or a transfer receipt
+        /// - if it received a transfer receipt and a transfer whitelist is set, the method checks the whitelist proof
+        /// - the method checks the token is a registered and active stakable, a transfer receipt of an inactive stakable can still be redeemed through `transfer_receipt_to_unstake`
+        /// - the method adds tokens to an internal vault, or burns the transfer receipt
+        /// - the method updates the staking ID (see `add_stake_to_id`)
+
+        fn stake_to_id(
+            &mut self,
+            id: &NonFungibleLocalId,
+            stake_bucket: Bucket,
+            whitelist_proof: Option<Proof>,
+        ) {
+            let id_data: Id = self.id_manager.get_non_fungible_data(id);
+            assert!(
+                id_data.next_period > self.current_period,
+                "Please claim unclaimed rewards on your ID before staking."
+            );
 
-            self.id_manager
-                .update_non_fungible_data(&id, "resources", resource_map);
+            let stake_amount: Decimal;
+            let address: ResourceAddress;
+
+            if stake_bucket.resource_address() == self.stake_transfer_receipt_manager.address() {
+                if let Some(whitelist_badge) = self.transfer_whitelist {
+                    whitelist_proof
+                        .expect("Whitelist badge required to redeem a stake transfer receipt.")
+                        .check_with_message(whitelist_badge, "Invalid whitelist badge supplied!");
+                }
+                let receipt_address: ResourceAddress = stake_bucket
+                    .as_non_fungible()
+                    .non_fungible::<StakeTransferReceipt>()
+                    .data()
+                    .address;
+                assert!(
+                    self.is_stakable(receipt_address),
+                    "Stakable is no longer active, redeem the receipt through transfer_receipt_to_unstake instead."
+                );
+                (stake_amount, address) = self.stake_transfer_receipt(stake_bucket.as_non_fungible());
+            } else {
+                assert!(
+                    self.is_stakable(stake_bucket.resource_address()),
+                    "Token is not stakable."
+                );
+                (stake_amount, address) = self.stake_tokens(stake_bucket);
+            }
 
-            self.reward_vault.take(stakable.lock.payment * amount_staked)
+            self.add_stake_to_id(id, address, stake_amount);
         }
 
-        //////////////////////////////////////////////////////////////////////
-        ////////////////////////////ADMIN METHODS/////////////////////////////
-        //////////////////////////////////////////////////////////////////////
+        /// This method adds tokens already held by the stakable's vault to the stake of a staking ID.
+        /// 
+        /// ## INPUT
+        /// - `id`: the staking ID
+        /// - `address`: the address of the stakable token
+        /// - `stake_amount`: the amount of staked tokens
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method checks staking is not paused, every way of adding stake passes through this method
+        /// - the method updates the staking ID's stake of the token and its last stake period
+        /// - the method updates the stakable's total stake
+        /// - the method sets the staking ID's next period to the next period, as all its rewards have been claimed
 
-        pub fn set_period_interval(&mut self, new_interval: i64) {
-            self.period_interval = new_interval;
-        }
+        fn add_stake_to_id(&mut self, id: &NonFungibleLocalId, address: ResourceAddress, stake_amount: Decimal) {
+            assert!(!self.paused, "Staking is paused.");
 
-        pub fn fill_rewards(&mut self, bucket: Bucket) {
-            self.reward_vault.put(bucket.as_fungible());
-        }
+            let id_data: Id = self.id_manager.get_non_fungible_data(id);
+            let mut resource_map = id_data.resources.clone();
+            let current_period: i64 = self.current_period;
+            resource_map.entry(address)
+                .and_modify(|resource| {
+                    resource.amount_staked += stake_amount;
+                    resource.last_stake_period = current_period;
+                })
+                .or_insert(Resource {
+                    amount_staked: stake_amount,
+                    locked_until: None,
+                    amount_locked: dec!(0),
+                    last_stake_period: current_period,
+                    lock_multiplier: dec!(1),
+                    counted_locked: dec!(0),
+                    lock_end_period: -1,
+                    dao_locked_until: None,
+                    lock_payment: dec!(0),
+                    amount_unstaking: dec!(0),
+                    dao_counted: dec!(0),
+                    dao_lock_end_period: -1,
+                });
 
-        pub fn remove_rewards(&mut self, amount: Decimal) -> Bucket {
-            self.reward_vault.take(amount).into()
-        }
+            self.id_manager
+                .update_non_fungible_data(id, "resources", resource_map);
 
-        pub fn set_max_claim_delay(&mut self, new_delay: i64) {
-            self.max_claim_delay = new_delay;
-        }
+            self.stakes.get_mut(&address).unwrap().amount_staked += stake_amount;
 
-        pub fn set_unstake_delay(&mut self, new_delay: i64) {
-            assert!(new_delay <= self.max_unstaking_delay, "Unstaking delay cannot be longer than the maximum unstaking delay.");
-            self.unstake_delay = new_delay;
+            self.id_manager.update_non_fungible_data(
+                id,
+                "next_period",
+                self.current_period + 1,
+            );
         }
 
-        pub fn set_rewards(&mut self, address: ResourceAddress, reward: Decimal) {
-            self.stakes.get_mut(&address).unwrap().reward_amount = reward;
+        /// This method returns the last period a lock ending at a given moment fully covers.
+        /// 
+        /// ## INPUT
+        /// - `lock_until`: the end of the lock
+        ///
+        /// ## OUTPUT
+        /// - the last period ending no later than the lock, the period before the current period if the lock ends within the current period
+        /// 
+        /// ## LOGIC
+        /// - the current period ends at the next period, every later period one period interval after the previous one
+
+        fn lock_end_period(&self, lock_until: Instant) -> i64 {
+            let remaining: i64 = lock_until.seconds_since_unix_epoch - self.next_period.seconds_since_unix_epoch;
+
+            if remaining < 0 {
+                return self.current_period - 1;
+            }
+
+            self.current_period + remaining / (self.period_interval * 86400)
         }
 
-        pub fn add_stakable(&mut self, address: ResourceAddress, reward_amount: Decimal, lock: Lock) {
-            self.stakes.insert(
-                address,
-                StakableUnit {
-                    address,
-                    amount_staked: dec!(0),
-                    vault: Vault::new(address),
-                    reward_amount,
-                    lock,
-                    rewards: KeyValueStore::new(),
-                },
-            );
+        /// This method replaces a staking ID's reward lock of a stakable, updating the stakable's locked totals.
+        /// 
+        /// ## INPUT
+        /// - `address`: the address of the stakable token
+        /// - `resource`: the staking ID's resource entry of the token, updated in place
+        /// - `counted_locked`: the locked tokens earning the lock multiplier
+        /// - `lock_multiplier`: the multiplier of the lock
+        /// - `lock_end_period`: the last period the lock fully covers
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - unless it has already expired, the staking ID's previous reward lock is removed from the stakable's locked amount and locked weight, and from the expiry of its last period
+        /// - unless it covers no period from the current period on, the new reward lock is added to the stakable's locked amount and locked weight, and scheduled to expire after its last period
+        /// - expiries are subtracted from the locked totals once their period is recorded, so an expired lock never has to be synced
+        /// - the staking ID's resource entry records the new reward lock, weighting its rewards for the periods up to the lock's last period
+        /// - the staking ID's DAO locked tokens are capped at its staked amount and recounted around the new reward lock (see `count_dao_lock`)
+
+        fn count_lock(
+            &mut self,
+            address: ResourceAddress,
+            resource: &mut Resource,
+            counted_locked: Decimal,
+            lock_multiplier: Decimal,
+            lock_end_period: i64,
+        ) {
+            self.count_dao_lock(address, resource, false);
+
+            let current_period: i64 = self.current_period;
+            let stakable = self.stakes.get_mut(&address).unwrap();
+
+            if resource.lock_end_period >= current_period && resource.counted_locked > dec!(0) {
+                let weight: Decimal = resource.counted_locked * resource.lock_multiplier;
+                stakable.locked_amount -= resource.counted_locked;
+                stakable.locked_weight -= weight;
+                let (amount, expiring_weight) = *stakable
+                    .lock_expiries
+                    .get(&resource.lock_end_period)
+                    .unwrap();
+                stakable.lock_expiries.insert(
+                    resource.lock_end_period,
+                    (amount - resource.counted_locked, expiring_weight - weight),
+                );
+            }
+
+            if lock_end_period >= current_period && counted_locked > dec!(0) {
+                let weight: Decimal = counted_locked * lock_multiplier;
+                stakable.locked_amount += counted_locked;
+                stakable.locked_weight += weight;
+                let (amount, expiring_weight) = stakable
+                    .lock_expiries
+                    .get(&lock_end_period)
+                    .map_or((dec!(0), dec!(0)), |expiry| *expiry);
+                stakable.lock_expiries.insert(
+                    lock_end_period,
+                    (amount + counted_locked, expiring_weight + weight),
+                );
+            }
+
+            resource.counted_locked = counted_locked;
+            resource.lock_multiplier = lock_multiplier;
+            resource.lock_end_period = lock_end_period;
+            resource.dao_counted = resource.dao_counted.min(resource.amount_staked);
+
+            self.count_dao_lock(address, resource, true);
         }
 
-        pub fn edit_stakable(&mut self, address: ResourceAddress, reward_amount: Decimal, lock: Lock) {
+        /// This method adds a staking ID's DAO locked tokens to its stakable's DAO locked total, or removes them from it.
+        /// 
+        /// ## INPUT
+        /// - `address`: the address of the stakable token
+        /// - `resource`: the staking ID's resource entry of the token
+        /// - `add`: whether to add the DAO locked tokens, removing them otherwise
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method counts the DAO locked tokens from the current period on (see `Resource::dao_lock_counts`), so changes already applied by recorded periods are left alone
+        /// - the method adds the count to the stakable's DAO locked total and schedules its later changes, or reverts both
+        /// - to change a DAO lock, it is removed before the staking ID's resource entry changes and added again afterwards
+
+        fn count_dao_lock(&mut self, address: ResourceAddress, resource: &Resource, add: bool) {
+            let (amount, changes) = resource.dao_lock_counts(self.current_period);
+            let sign: Decimal = if add { dec!(1) } else { dec!(-1) };
             let stakable = self.stakes.get_mut(&address).unwrap();
-            stakable.reward_amount = reward_amount;
-            stakable.lock = lock;
+
+            stakable.dao_locked_amount += sign * amount;
+            for (period, change) in changes {
+                let scheduled: Decimal = stakable
+                    .dao_lock_expiries
+                    .get(&period)
+                    .map_or(dec!(0), |scheduled| *scheduled);
+                stakable.dao_lock_expiries.insert(period, scheduled + sign * change);
+            }
         }
 
-        pub fn set_next_period_to_now(&mut self) {
-            self.next_period = Clock::current_time_rounded_to_minutes();
+        /// This method caps a staking ID's locked tokens earning the lock multiplier at its staked amount, after its stake decreased.
+        /// 
+        /// ## INPUT
+        /// - `address`: the address of the stakable token
+        /// - `resource`: the staking ID's resource entry of the token, updated in place
+        ///
+        /// ## OUTPUT
+        /// - none
+        /// 
+        /// ## LOGIC
+        /// - the method keeps the reward lock's multiplier and last period, only lowering its counted locked tokens (see `count_lock`)
+
+        fn recount_lock(&mut self, address: ResourceAddress, resource: &mut Resource) {
+            let counted_locked: Decimal = resource.counted_locked.min(resource.amount_staked);
+            let (lock_multiplier, lock_end_period) = (resource.lock_multiplier, resource.lock_end_period);
+            self.count_lock(address, resource, counted_locked, lock_multiplier, lock_end_period);
         }
 
-        // This method locks staked tokens for voting
-        //
-        // ## INPUT
-        // - `address`: the address of the stakable token
-        // - `lock_until`: the date until which the tokens are locked
-        // - `id`: the staking ID
-        //
-        // ## OUTPUT
-        // - none
-        //
-        // ## LOGIC
-        // - the method checks whether a DAO is controlling the staking
-        // - the method updates the locked_until field of the staking ID appropriately
-        
-        pub fn set_lock(&mut self, address: ResourceAddress, lock_until: Instant, id: NonFungibleLocalId) {
-            assert!(self.dao_controlled, "This functionality is only available if a DAO is controlling the staking.");
-            let id_data: Id = self.id_manager.get_non_fungible_data(&id);
+        /// This method burns an unstake receipt and releases its bookkeeping.
+        /// 
+        /// ## INPUT
+        /// - `receipt`: the unstake receipt
+        ///
+        /// ## OUTPUT
+        /// - the data of the burnt receipt
+        /// 
+        /// ## LOGIC
+        /// - the method burns the receipt
+        /// - the method frees up an outstanding unstake slot on the staking ID that requested the unstake and removes the receipt's tokens from its unstaking amount
+        /// - the method removes the receipt's tokens from the stakable's unstaking amount
 
-            let mut resource_map = id_data.resources.clone();
-            let mut resource = resource_map
-                .get(&address)
-                .expect("Stakable not found in staking ID.")
-                .clone();
-               
-            resource.locked_until = Some(lock_until);
-            resource_map.insert(address, resource);
+        fn burn_unstake_receipt(&mut self, receipt: Bucket) -> UnstakeReceipt {
+            let receipt_data = receipt
+                .as_non_fungible()
+                .non_fungible::<UnstakeReceipt>()
+                .data();
+            receipt.burn();
 
-            self.id_manager
-                .update_non_fungible_data(&id, "resources", resource_map);
-        }
+            if let Some(id) = &receipt_data.id {
+                let id_data: Id = self.id_manager.get_non_fungible_data(id);
+                self.id_manager.update_non_fungible_data(
+                    id,
+                    "outstanding_unstakes",
+                    id_data.outstanding_unstakes.saturating_sub(1),
+                );
 
-        //////////////////////////////////////////////////////////////////////
-        ////////////////////////////HELPER METHODS////////////////////////////
-        //////////////////////////////////////////////////////////////////////
+                let mut resource_map = id_data.resources.clone();
+                if let Some(resource) = resource_map.get_mut(&receipt_data.address) {
+                    resource.amount_unstaking -= receipt_data.amount;
+                }
+                self.id_manager
+                    .update_non_fungible_data(id, "resources", resource_map);
+            }
+
+            self.stakes.get_mut(&receipt_data.address).unwrap().unstaking_amount -= receipt_data.amount;
+
+            receipt_data
+        }
 
         /// This method counts the staked tokens and puts them away in the staking component's vault.
         /// 
@@ -736,17 +3908,16 @@ mod staking {
         /// ## LOGIC
         /// - the method checks whether the staked token is a stakable token
         /// - the method puts the staked tokens in the staking component's vault
+        /// - the method counts the staked tokens as the increase of the vault balance, so tokens charging a fee on transfer are not overcounted
         /// - the method returns the amount of staked tokens and the address of the stakable token
 
         fn stake_tokens(&mut self, stake_bucket: Bucket) -> (Decimal, ResourceAddress) {   
             let address: ResourceAddress = stake_bucket.resource_address();
             assert!(self.stakes.get(&address).is_some(), "Token supplied does not match requested stakable token.");
-            let stake_amount: Decimal = stake_bucket.amount();
-            self.stakes
-                .get_mut(&address)
-                .unwrap()
-                .vault
-                .put(stake_bucket);
+            let vault: &mut Vault = &mut self.stakes.get_mut(&address).unwrap().vault;
+            let amount_before: Decimal = vault.amount();
+            vault.put(stake_bucket);
+            let stake_amount: Decimal = vault.amount() - amount_before;
 
             (stake_amount, address)
         }
@@ -1,68 +1,2724 @@
+use radix_engine::transaction::TransactionReceipt;
 use radix_engine_interface::prelude::*;
 use scrypto::this_package;
 use scrypto_test::prelude::*;
 use scrypto_unit::*;
 
-use staker_package::test_bindings::*;
+// Mirror of the blueprint's lock configuration, passed in manifests and read back from method outputs.
+#[derive(ManifestSbor, ScryptoSbor, Clone)]
+struct Lock {
+    payment: Decimal,
+    duration: i64,
+    multiplier: Decimal,
+}
+
+// Mirror of the blueprint's stake of a token on a staking ID, read back from method outputs.
+#[derive(ScryptoSbor)]
+#[allow(dead_code)]
+struct Resource {
+    amount_staked: Decimal,
+    locked_until: Option<Instant>,
+    amount_locked: Decimal,
+    last_stake_period: i64,
+    lock_multiplier: Decimal,
+    counted_locked: Decimal,
+    lock_end_period: i64,
+    dao_locked_until: Option<Instant>,
+    lock_payment: Decimal,
+    amount_unstaking: Decimal,
+    dao_counted: Decimal,
+    dao_lock_end_period: i64,
+}
+
+// Mirror of the blueprint's staking ID data, read back from method outputs.
+#[derive(ScryptoSbor)]
+#[allow(dead_code)]
+struct Id {
+    resources: HashMap<ResourceAddress, Resource>,
+    next_period: i64,
+    authorized_staker: Option<ResourceAddress>,
+    label: String,
+    outstanding_unstakes: u64,
+    airdrops: HashMap<ResourceAddress, Decimal>,
+    sweepable: bool,
+    last_claim: Option<Instant>,
+    total_claimed: Decimal,
+}
+
+// Manifest mirror of the blueprint's emission curve.
+#[derive(ManifestSbor)]
+#[allow(dead_code)]
+enum EmissionCurve {
+    Flat,
+    SquareRoot,
+}
+
+const REWARD_AMOUNT: Decimal = dec!(100);
+const INITIAL_REWARDS: Decimal = dec!(10000);
+
+fn default_lock() -> Lock {
+    Lock {
+        payment: dec!(1),
+        duration: 30,
+        multiplier: dec!(1),
+    }
+}
+
+fn expect_failure(receipt: &TransactionReceipt, message: &str) {
+    receipt.expect_specific_failure(|error| format!("{:?}", error).contains(message));
+}
+
+#[derive(Default)]
+struct Setup {
+    dao_controlled: bool,
+    transferable_ids: bool,
+    owned_by_other_badge: bool,
+}
+
+struct TestEnv {
+    runner: DefaultTestRunner,
+    public_key: Secp256k1PublicKey,
+    account: ComponentAddress,
+    controller: ResourceAddress,
+    other_badge: ResourceAddress,
+    owner_badge: ResourceAddress,
+    reward: ResourceAddress,
+    token: ResourceAddress,
+    component: ComponentAddress,
+    id_resource: ResourceAddress,
+    transfer_receipt: ResourceAddress,
+    unstake_receipt: ResourceAddress,
+    vesting_receipt: ResourceAddress,
+    ids: u64,
+    round: u64,
+}
+
+impl TestEnv {
+    fn new() -> Self {
+        Self::with(Setup::default())
+    }
+
+    // Instantiates a staking component with a daily period, paying REWARD_AMOUNT per period for staking `token`.
+    fn with(setup: Setup) -> Self {
+        let mut runner = TestRunnerBuilder::new().without_trace().build();
+        let (public_key, _private_key, account) = runner.new_allocated_account();
+        let package = runner.compile_and_publish(this_package!());
+
+        let controller = runner.create_fungible_resource(dec!(1), 0, account);
+        let other_badge = runner.create_fungible_resource(dec!(1), 0, account);
+        let reward = runner.create_fungible_resource(dec!(1000000), 18, account);
+        let token = runner.create_fungible_resource(dec!(1000000), 18, account);
+
+        let (owner_rule, owner_badge): (Option<AccessRule>, ResourceAddress) = if setup.owned_by_other_badge {
+            (Some(rule!(require(other_badge))), other_badge)
+        } else {
+            (None, controller)
+        };
+
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(account, reward, INITIAL_REWARDS)
+            .take_all_from_worktop(reward, "rewards")
+            .call_function_with_name_lookup(package, "Staking", "new", |lookup| {
+                (
+                    controller,
+                    lookup.bucket("rewards"),
+                    1i64,
+                    "Test".to_string(),
+                    "TST".to_string(),
+                    setup.dao_controlled,
+                    30i64,
+                    365i64,
+                    owner_rule,
+                    None::<ResourceAddress>,
+                    setup.transferable_ids,
+                )
+            })
+            .build();
+        let receipt = runner.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&public_key)],
+        );
+        let commit = receipt.expect_commit_success();
+        let component = commit.new_component_addresses()[0];
+        let resources = commit.new_resource_addresses();
+
+        let mut env = Self {
+            runner,
+            public_key,
+            account,
+            controller,
+            other_badge,
+            owner_badge,
+            reward,
+            token,
+            component,
+            id_resource: resources[0],
+            transfer_receipt: resources[1],
+            unstake_receipt: resources[2],
+            vesting_receipt: resources[3],
+            ids: 0,
+            round: 0,
+        };
+
+        env.owner_call("add_stakable", manifest_args!(token, REWARD_AMOUNT, default_lock()))
+            .expect_commit_success();
+
+        env
+    }
+
+    fn execute(&mut self, manifest: TransactionManifestV1) -> TransactionReceipt {
+        self.runner.execute_manifest(
+            manifest,
+            vec![NonFungibleGlobalId::from_public_key(&self.public_key)],
+        )
+    }
+
+    fn deposit(&self, builder: ManifestBuilder) -> ManifestBuilder {
+        builder.call_method(
+            self.account,
+            "deposit_batch",
+            manifest_args!(ManifestExpression::EntireWorktop),
+        )
+    }
+
+    fn call<A: ResolvableArguments>(&mut self, method: &str, args: A) -> TransactionReceipt {
+        let builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(self.component, method, args);
+        let manifest = self.deposit(builder).build();
+        self.execute(manifest)
+    }
+
+    fn owner_call<A: ResolvableArguments>(&mut self, method: &str, args: A) -> TransactionReceipt {
+        let builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(self.account, self.owner_badge, dec!(1))
+            .call_method(self.component, method, args);
+        let manifest = self.deposit(builder).build();
+        self.execute(manifest)
+    }
+
+    fn owner_call_with_bucket<A: ResolvableArguments>(
+        &mut self,
+        resource: ResourceAddress,
+        amount: Decimal,
+        method: &str,
+        args: impl FnOnce(ManifestBucket) -> A,
+    ) -> TransactionReceipt {
+        let builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(self.account, self.owner_badge, dec!(1))
+            .withdraw_from_account(self.account, resource, amount)
+            .take_all_from_worktop(resource, "bucket")
+            .call_method_with_name_lookup(self.component, method, |lookup| {
+                args(lookup.bucket("bucket"))
+            });
+        let manifest = self.deposit(builder).build();
+        self.execute(manifest)
+    }
+
+    fn read<T: ScryptoDecode, A: ResolvableArguments>(&mut self, method: &str, args: A) -> T {
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(self.component, method, args)
+            .build();
+        self.execute(manifest).expect_commit_success().output::<T>(1)
+    }
+
+    fn call_with_id<A: ResolvableArguments>(
+        &mut self,
+        id: &NonFungibleLocalId,
+        method: &str,
+        args: impl FnOnce(ManifestProof) -> A,
+    ) -> TransactionReceipt {
+        let builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_non_fungibles(self.account, self.id_resource, vec![id.clone()])
+            .pop_from_auth_zone("id_proof")
+            .call_method_with_name_lookup(self.component, method, |lookup| {
+                args(lookup.proof("id_proof"))
+            });
+        let manifest = self.deposit(builder).build();
+        self.execute(manifest)
+    }
+
+    fn call_with_bucket<A: ResolvableArguments>(
+        &mut self,
+        resource: ResourceAddress,
+        amount: Decimal,
+        method: &str,
+        args: impl FnOnce(ManifestBucket) -> A,
+    ) -> TransactionReceipt {
+        let builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.account, resource, amount)
+            .take_all_from_worktop(resource, "bucket")
+            .call_method_with_name_lookup(self.component, method, |lookup| {
+                args(lookup.bucket("bucket"))
+            });
+        let manifest = self.deposit(builder).build();
+        self.execute(manifest)
+    }
+
+    fn call_with_bucket_and_id<A: ResolvableArguments>(
+        &mut self,
+        resource: ResourceAddress,
+        amount: Decimal,
+        id: &NonFungibleLocalId,
+        method: &str,
+        args: impl FnOnce(ManifestBucket, ManifestProof) -> A,
+    ) -> TransactionReceipt {
+        let builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.account, resource, amount)
+            .take_all_from_worktop(resource, "bucket")
+            .create_proof_from_account_of_non_fungibles(self.account, self.id_resource, vec![id.clone()])
+            .pop_from_auth_zone("id_proof")
+            .call_method_with_name_lookup(self.component, method, |lookup| {
+                args(lookup.bucket("bucket"), lookup.proof("id_proof"))
+            });
+        let manifest = self.deposit(builder).build();
+        self.execute(manifest)
+    }
+
+    fn call_with_nft<A: ResolvableArguments>(
+        &mut self,
+        resource: ResourceAddress,
+        nft: &NonFungibleLocalId,
+        method: &str,
+        args: impl FnOnce(ManifestBucket) -> A,
+    ) -> TransactionReceipt {
+        let builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.account, resource, vec![nft.clone()])
+            .take_all_from_worktop(resource, "bucket")
+            .call_method_with_name_lookup(self.component, method, |lookup| {
+                args(lookup.bucket("bucket"))
+            });
+        let manifest = self.deposit(builder).build();
+        self.execute(manifest)
+    }
+
+    // Stakes tokens to a new staking ID and returns the ID.
+    fn stake_new_id(&mut self, resource: ResourceAddress, amount: Decimal) -> NonFungibleLocalId {
+        self.call_with_bucket(resource, amount, "stake", |bucket| {
+            (bucket, None::<ManifestProof>, None::<ManifestProof>)
+        })
+        .expect_commit_success();
+        self.ids += 1;
+        NonFungibleLocalId::integer(self.ids)
+    }
+
+    fn stake(&mut self, amount: Decimal) -> NonFungibleLocalId {
+        let token = self.token;
+        self.stake_new_id(token, amount)
+    }
+
+    fn stake_to(&mut self, id: &NonFungibleLocalId, amount: Decimal) -> TransactionReceipt {
+        let token = self.token;
+        self.call_with_bucket_and_id(token, amount, id, "stake", |bucket, proof| {
+            (bucket, Some(proof), None::<ManifestProof>)
+        })
+    }
+
+    fn start_unstake(
+        &mut self,
+        id: &NonFungibleLocalId,
+        amount: Decimal,
+        stake_transfer: bool,
+    ) -> TransactionReceipt {
+        let token = self.token;
+        self.call_with_id(id, "start_unstake", |proof| {
+            (proof, token, amount, stake_transfer, false, false)
+        })
+    }
+
+    fn update_id(&mut self, id: &NonFungibleLocalId) -> TransactionReceipt {
+        self.call_with_id(id, "update_id", |proof| (proof,))
+    }
+
+    // Claims the rewards of a staking ID, returning the increase of the account's reward balance.
+    fn claim(&mut self, id: &NonFungibleLocalId) -> Decimal {
+        let before = self.balance(self.reward);
+        self.update_id(id).expect_commit_success();
+        self.balance(self.reward) - before
+    }
+
+    fn update_period(&mut self) -> TransactionReceipt {
+        self.call("update_period", manifest_args!())
+    }
+
+    fn last_unstake_receipt(&mut self) -> NonFungibleLocalId {
+        let (unstake_counter, _): (u64, u64) = self.read("receipt_counters", manifest_args!());
+        NonFungibleLocalId::integer(unstake_counter)
+    }
+
+    fn last_transfer_receipt(&mut self) -> NonFungibleLocalId {
+        let (_, transfer_counter): (u64, u64) = self.read("receipt_counters", manifest_args!());
+        NonFungibleLocalId::integer(transfer_counter)
+    }
+
+    fn finish_unstake(&mut self, receipt: &NonFungibleLocalId) -> TransactionReceipt {
+        let unstake_receipt = self.unstake_receipt;
+        self.call_with_nft(unstake_receipt, receipt, "finish_unstake", |bucket| (bucket,))
+    }
+
+    fn staked(&mut self, id: &NonFungibleLocalId, resource: ResourceAddress) -> Decimal {
+        let balances: Vec<(ResourceAddress, Decimal)> =
+            self.read("staked_balances", manifest_args!(id.clone()));
+        balances
+            .into_iter()
+            .find(|(address, _)| *address == resource)
+            .map_or(dec!(0), |(_, amount)| amount)
+    }
+
+    fn balance(&mut self, resource: ResourceAddress) -> Decimal {
+        self.runner.get_component_balance(self.account, resource)
+    }
+
+    fn advance_minutes(&mut self, minutes: i64) {
+        let now = self.runner.get_current_proposer_timestamp_ms();
+        self.round += 1;
+        self.runner
+            .advance_to_round_at_timestamp(Round::of(self.round), now + minutes * 60_000)
+            .expect_commit_success();
+    }
+
+    fn advance_days(&mut self, days: i64) {
+        self.advance_minutes(days * 1440);
+    }
+}
+
+//////////////////////////////////////////////////////////////////////
+/////////////////////////////////SETUP////////////////////////////////
+//////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_setup_info_matches_instantiation() {
+    let mut env = TestEnv::with(Setup {
+        dao_controlled: true,
+        ..Setup::default()
+    });
+
+    let (reward_resource, reward_balance): (ResourceAddress, Decimal) =
+        env.read("reward_info", manifest_args!());
+    assert_eq!(reward_resource, env.reward);
+    assert_eq!(reward_balance, INITIAL_REWARDS);
+
+    let dao_controlled: bool = env.read("is_dao_controlled", manifest_args!());
+    assert!(dao_controlled);
+
+    let (unstake_delay, max_unstaking_delay): (i64, i64) =
+        env.read("unstake_delay_info", manifest_args!());
+    assert_eq!(unstake_delay, 7);
+    assert_eq!(max_unstaking_delay, 30);
+
+    let controller: ResourceAddress = env.read("get_controller", manifest_args!());
+    assert_eq!(controller, env.controller);
+}
+
+#[test]
+fn test_add_stakable_twice_fails() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+
+    let receipt = env.owner_call("add_stakable", manifest_args!(token, REWARD_AMOUNT, default_lock()));
+    expect_failure(&receipt, "Stakable already added.");
+}
+
+#[test]
+fn test_owner_methods_require_owner() {
+    let mut env = TestEnv::new();
+
+    let receipt = env.call("set_keeper_reward", manifest_args!(dec!(1)));
+    receipt.expect_commit_failure();
+}
+
+#[test]
+fn test_fill_rewards_rejects_other_token() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+
+    let receipt = env.owner_call_with_bucket(token, dec!(10), "fill_rewards", |bucket| (bucket,));
+    expect_failure(&receipt, "Rewards must be the fungible reward token.");
+}
+
+#[test]
+fn test_update_branding_sets_metadata() {
+    let mut env = TestEnv::new();
+    env.owner_call("update_branding", manifest_args!("New".to_string(), "NEW".to_string()))
+        .expect_commit_success();
+
+    let id_resource = env.id_resource;
+    let unstake_receipt = env.unstake_receipt;
+    let transfer_receipt = env.transfer_receipt;
+    assert_eq!(
+        env.runner.get_metadata(id_resource.into(), "name"),
+        Some(MetadataValue::String("New Staking ID".to_string()))
+    );
+    assert_eq!(
+        env.runner.get_metadata(id_resource.into(), "symbol"),
+        Some(MetadataValue::String("idNEW".to_string()))
+    );
+    assert_eq!(
+        env.runner.get_metadata(unstake_receipt.into(), "description"),
+        Some(MetadataValue::String("An unstake receipt used in the New ecosystem.".to_string()))
+    );
+    assert_eq!(
+        env.runner.get_metadata(transfer_receipt.into(), "name"),
+        Some(MetadataValue::String("New Stake Transfer Receipt".to_string()))
+    );
+
+    let branding: (String, String) = env.read("branding", manifest_args!());
+    assert_eq!(branding, ("New".to_string(), "NEW".to_string()));
+    let receipt = env.call("update_branding", manifest_args!("Other".to_string(), "OTH".to_string()));
+    receipt.expect_commit_failure();
+}
+
+#[test]
+fn test_all_stakable_configs_lists_every_stakable() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let account = env.account;
+    let other = env.runner.create_fungible_resource(dec!(1000), 18, account);
+    env.owner_call("add_stakable", manifest_args!(other, dec!(50), double_weight_lock()))
+        .expect_commit_success();
+
+    let configs: Vec<(ResourceAddress, Decimal, Lock)> =
+        env.read("all_stakable_configs", manifest_args!());
+    assert_eq!(configs.len(), 2);
+    let (_, reward_amount, lock) = configs.iter().find(|(address, _, _)| *address == token).unwrap();
+    assert_eq!(*reward_amount, REWARD_AMOUNT);
+    assert_eq!(lock.duration, 30);
+    assert_eq!(lock.payment, dec!(1));
+    let (_, reward_amount, lock) = configs.iter().find(|(address, _, _)| *address == other).unwrap();
+    assert_eq!(*reward_amount, dec!(50));
+    assert_eq!(lock.duration, 2);
+    assert_eq!(lock.multiplier, dec!(2));
+}
+
+//////////////////////////////////////////////////////////////////////
+////////////////////////////////PERIODS///////////////////////////////
+//////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_keeper_reward_paid_only_when_period_advances() {
+    let mut env = TestEnv::new();
+    env.owner_call("set_keeper_reward", manifest_args!(dec!(1)))
+        .expect_commit_success();
+
+    let before = env.balance(env.reward);
+    env.update_period().expect_commit_success();
+    assert_eq!(env.balance(env.reward), before);
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + dec!(1));
+
+    env.update_period().expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + dec!(1));
+}
+
+#[test]
+fn test_keeper_reward_capped_to_unreserved_rewards() {
+    let mut env = TestEnv::new();
+    env.stake(dec!(100));
+    env.owner_call("set_keeper_reward", manifest_args!(dec!(1000000)))
+        .expect_commit_success();
+
+    let before = env.balance(env.reward);
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+    let keeper_reward = INITIAL_REWARDS - REWARD_AMOUNT;
+    assert_eq!(env.balance(env.reward), before + keeper_reward);
+    let distributed: Decimal = env.read("total_distributed", manifest_args!());
+    assert_eq!(distributed, keeper_reward);
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + keeper_reward);
+    let distributed: Decimal = env.read("total_distributed", manifest_args!());
+    assert_eq!(distributed, keeper_reward);
+}
+
+#[test]
+fn test_no_keeper_reward_by_default() {
+    let mut env = TestEnv::new();
+
+    let before = env.balance(env.reward);
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+    assert_eq!(env.balance(env.reward), before);
+}
+
+#[test]
+fn test_catch_up_limited_by_max_catchup_periods() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.stake(dec!(100));
+    env.owner_call("set_max_catchup_periods", manifest_args!(2i64))
+        .expect_commit_success();
+
+    env.advance_days(5);
+    env.update_period().expect_commit_success();
+
+    let recorded: Option<Decimal> = env.read("historical_staked", manifest_args!(token, 1i64));
+    assert_eq!(recorded, Some(dec!(100)));
+    let not_recorded: Option<Decimal> = env.read("historical_staked", manifest_args!(token, 2i64));
+    assert_eq!(not_recorded, None);
+
+    env.owner_call("catch_up_periods", manifest_args!(10i64))
+        .expect_commit_success();
+    let caught_up: Option<Decimal> = env.read("historical_staked", manifest_args!(token, 4i64));
+    assert_eq!(caught_up, Some(dec!(100)));
+    let future: Option<Decimal> = env.read("historical_staked", manifest_args!(token, 5i64));
+    assert_eq!(future, None);
+}
+
+//////////////////////////////////////////////////////////////////////
+/////////////////////////////////STAKING//////////////////////////////
+//////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_stake_creates_id_holding_stake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+
+    let id = env.stake(dec!(100));
+
+    assert_eq!(env.balance(env.id_resource), dec!(1));
+    assert_eq!(env.staked(&id, token), dec!(100));
+    let (vault, staked): (Decimal, Decimal) = env.read("stakable_balance", manifest_args!(token));
+    assert_eq!(vault, dec!(100));
+    assert_eq!(staked, dec!(100));
+}
+
+#[test]
+fn test_staking_requires_claimed_rewards() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let receipt = env.stake_to(&id, dec!(10));
+    expect_failure(&receipt, "Please claim unclaimed rewards on your ID before staking.");
+
+    env.update_id(&id).expect_commit_success();
+    env.stake_to(&id, dec!(10)).expect_commit_success();
+}
+
+#[test]
+fn test_inactive_stakable_rejects_stake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("set_stakable_active", manifest_args!(token, false))
+        .expect_commit_success();
+
+    let receipt = env.call_with_bucket(token, dec!(10), "stake", |bucket| {
+        (bucket, None::<ManifestProof>, None::<ManifestProof>)
+    });
+    expect_failure(&receipt, "Token is not stakable.");
+}
+
+#[test]
+fn test_pause_blocks_staking_but_not_finishing_unstake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let id = env.stake(dec!(100));
+    env.start_unstake(&id, dec!(50), false).expect_commit_success();
+    let receipt_id = env.last_unstake_receipt();
+
+    env.owner_call("set_paused", manifest_args!(true))
+        .expect_commit_success();
+    let receipt = env.stake_to(&id, dec!(10));
+    expect_failure(&receipt, "Staking is paused.");
+
+    env.advance_days(7);
+    let before = env.balance(token);
+    env.finish_unstake(&receipt_id).expect_commit_success();
+    assert_eq!(env.balance(token), before + dec!(50));
+}
+
+#[test]
+fn test_stake_for_authorized_staker() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let other_badge = env.other_badge;
+    let id = env.stake(dec!(100));
+
+    env.call_with_id(&id, "set_authorized_staker", |proof| (proof, Some(other_badge)))
+        .expect_commit_success();
+
+    let builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(env.account, token, dec!(25))
+        .take_all_from_worktop(token, "bucket")
+        .create_proof_from_account_of_amount(env.account, other_badge, dec!(1))
+        .pop_from_auth_zone("staker_proof")
+        .call_method_with_name_lookup(env.component, "stake_for", |lookup| {
+            (id.clone(), lookup.bucket("bucket"), lookup.proof("staker_proof"))
+        });
+    let manifest = env.deposit(builder).build();
+    env.execute(manifest).expect_commit_success();
+
+    assert_eq!(env.staked(&id, token), dec!(125));
+}
 
 #[test]
-fn test_hello() {
-    // Setup the environment
-    let mut test_runner = TestRunnerBuilder::new().build();
+fn test_label_length_is_limited() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
 
-    // Create an account
-    let (public_key, _private_key, account) = test_runner.new_allocated_account();
+    env.call_with_id(&id, "set_label", |proof| (proof, "savings".to_string()))
+        .expect_commit_success();
+    let receipt = env.call_with_id(&id, "set_label", |proof| (proof, "x".repeat(65)));
+    expect_failure(&receipt, "Label is too long.");
+}
+
+#[test]
+fn test_ids_not_transferable_by_default() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    let builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account, env.id_resource, vec![id]);
+    let manifest = env.deposit(builder).build();
+    env.execute(manifest).expect_commit_failure();
+}
 
-    // Publish package
-    let package_address = test_runner.compile_and_publish(this_package!());
+#[test]
+fn test_transferable_ids_move_between_accounts() {
+    let mut env = TestEnv::with(Setup {
+        transferable_ids: true,
+        ..Setup::default()
+    });
+    let id = env.stake(dec!(100));
+    let (other_key, _, other) = env.runner.new_allocated_account();
 
-    // Test the `instantiate_hello` function.
     let manifest = ManifestBuilder::new()
-        .call_function(
-            package_address,
-            "Hello",
-            "instantiate_hello",
-            manifest_args!(),
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account, env.id_resource, vec![id.clone()])
+        .call_method(
+            other,
+            "try_deposit_batch_or_abort",
+            manifest_args!(ManifestExpression::EntireWorktop, None::<ResourceOrNonFungible>),
         )
         .build();
-    let receipt = test_runner.execute_manifest_ignoring_fee(
-        manifest,
-        vec![NonFungibleGlobalId::from_public_key(&public_key)],
-    );
-    println!("{:?}\n", receipt);
-    let component = receipt.expect_commit(true).new_component_addresses()[0];
+    env.execute(manifest).expect_commit_success();
+    assert_eq!(env.runner.get_component_balance(other, env.id_resource), dec!(1));
+    assert_eq!(env.balance(env.id_resource), dec!(0));
 
-    // Test the `free_token` method.
-    let manifest = ManifestBuilder::new()
-        .call_method(component, "free_token", manifest_args!())
+    env.advance_days(1);
+    let builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_non_fungibles(other, env.id_resource, vec![id])
+        .pop_from_auth_zone("id_proof")
+        .call_method_with_name_lookup(env.component, "update_id", |lookup| {
+            (lookup.proof("id_proof"),)
+        });
+    let manifest = builder
         .call_method(
-            account,
-            "deposit_batch",
-            manifest_args!(ManifestExpression::EntireWorktop),
+            other,
+            "try_deposit_batch_or_abort",
+            manifest_args!(ManifestExpression::EntireWorktop, None::<ResourceOrNonFungible>),
         )
         .build();
-    let receipt = test_runner.execute_manifest_ignoring_fee(
-        manifest,
-        vec![NonFungibleGlobalId::from_public_key(&public_key)],
+    env.runner
+        .execute_manifest(manifest, vec![NonFungibleGlobalId::from_public_key(&other_key)])
+        .expect_commit_success();
+    assert_eq!(env.runner.get_component_balance(other, env.reward), REWARD_AMOUNT);
+}
+
+#[test]
+fn test_ids_in_range_reads_page_of_balances() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let first = env.stake(dec!(100));
+    let second = env.stake(dec!(200));
+    let third = env.stake(dec!(300));
+
+    let page: Vec<(NonFungibleLocalId, Decimal)> =
+        env.read("ids_in_range", manifest_args!(token, 2u64, 10u64));
+    assert_eq!(page, vec![(second, dec!(200)), (third, dec!(300))]);
+    let page: Vec<(NonFungibleLocalId, Decimal)> =
+        env.read("ids_in_range", manifest_args!(token, 0u64, 2u64));
+    assert_eq!(page, vec![(first, dec!(100))]);
+
+    let receipt = env.call("ids_in_range", manifest_args!(token, 0u64, 101u64));
+    expect_failure(&receipt, "Range exceeds the maximum page size.");
+}
+
+#[test]
+fn test_stake_credits_vault_increase() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let first = env.stake(dec!(100));
+
+    let (vault_before, staked_before): (Decimal, Decimal) =
+        env.read("stakable_balance", manifest_args!(token));
+    let second = env.stake(dec!("250.5"));
+    env.stake_to(&first, dec!(50)).expect_commit_success();
+    let (vault_after, staked_after): (Decimal, Decimal) =
+        env.read("stakable_balance", manifest_args!(token));
+
+    assert_eq!(vault_after - vault_before, dec!("300.5"));
+    assert_eq!(staked_after - staked_before, vault_after - vault_before);
+    assert_eq!(env.staked(&first, token), dec!(150));
+    assert_eq!(env.staked(&second, token), dec!("250.5"));
+}
+
+#[test]
+fn test_health_check_flags_drained_vault() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let account = env.account;
+    let recallable = env.runner.create_recallable_token(account);
+    env.owner_call("add_stakable", manifest_args!(recallable, REWARD_AMOUNT, default_lock()))
+        .expect_commit_success();
+    env.stake(dec!(100));
+    env.stake_new_id(recallable, dec!(2));
+
+    let health: Vec<(ResourceAddress, bool)> = env.read("health_check", manifest_args!());
+    assert!(health.iter().all(|(_, solvent)| *solvent));
+    env.call("assert_solvent", manifest_args!()).expect_commit_success();
+
+    let component = env.component;
+    let vault = env
+        .runner
+        .get_component_vaults(component, recallable)
+        .into_iter()
+        .find(|vault| env.runner.inspect_vault_balance(*vault) == Some(dec!(2)))
+        .unwrap();
+    let builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .recall(InternalAddress::new_or_panic(vault.into()), dec!(1));
+    let manifest = env.deposit(builder).build();
+    env.execute(manifest).expect_commit_success();
+
+    let health: Vec<(ResourceAddress, bool)> = env.read("health_check", manifest_args!());
+    assert!(health.contains(&(token, true)));
+    assert!(health.contains(&(recallable, false)));
+    let receipt = env.call("assert_solvent", manifest_args!());
+    receipt.expect_commit_failure();
+}
+
+#[test]
+fn test_stake_share_of_ids() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let first = env.stake(dec!(100));
+    let second = env.stake(dec!(300));
+
+    let share: Decimal = env.read("stake_share", manifest_args!(first, token));
+    assert_eq!(share, dec!("0.25"));
+    let share: Decimal = env.read("stake_share", manifest_args!(second.clone(), token));
+    assert_eq!(share, dec!("0.75"));
+
+    env.start_unstake(&second, dec!(300), false).expect_commit_success();
+    let share: Decimal = env.read("stake_share", manifest_args!(second, token));
+    assert_eq!(share, dec!(0));
+}
+
+#[test]
+fn test_is_stakable_reflects_registration_and_activity() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+
+    let stakable: bool = env.read("is_stakable", manifest_args!(token));
+    assert!(stakable);
+    let stakable: bool = env.read("is_stakable", manifest_args!(reward));
+    assert!(!stakable);
+
+    env.owner_call("set_stakable_active", manifest_args!(token, false))
+        .expect_commit_success();
+    let stakable: bool = env.read("is_stakable", manifest_args!(token));
+    assert!(!stakable);
+}
+
+#[test]
+fn test_stake_returning_id_returns_updated_id() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+
+    let (id_bucket, id_data): (Option<Own>, Id) = env
+        .call_with_bucket(token, dec!(100), "stake_returning_id", |bucket| {
+            (bucket, None::<ManifestProof>, None::<ManifestProof>)
+        })
+        .expect_commit_success()
+        .output(3);
+    env.ids += 1;
+    let id = NonFungibleLocalId::integer(env.ids);
+    assert!(id_bucket.is_some());
+    assert_eq!(id_data.resources.get(&token).unwrap().amount_staked, dec!(100));
+    assert_eq!(id_data.next_period, 1);
+
+    env.advance_days(1);
+    env.claim(&id);
+    let (id_bucket, id_data): (Option<Own>, Id) = env
+        .call_with_bucket_and_id(token, dec!(50), &id, "stake_returning_id", |bucket, proof| {
+            (bucket, Some(proof), None::<ManifestProof>)
+        })
+        .expect_commit_success()
+        .output(5);
+    assert!(id_bucket.is_none());
+    let resource = id_data.resources.get(&token).unwrap();
+    assert_eq!(resource.amount_staked, dec!(150));
+    assert_eq!(resource.last_stake_period, 1);
+    assert_eq!(id_data.next_period, 2);
+}
+
+//////////////////////////////////////////////////////////////////////
+/////////////////////////////////REWARDS//////////////////////////////
+//////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_single_staker_earns_full_period_reward() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let preview: Decimal = env.read("preview_rewards", manifest_args!(id.clone()));
+    assert_eq!(preview, REWARD_AMOUNT);
+    assert_eq!(env.claim(&id), REWARD_AMOUNT);
+}
+
+#[test]
+fn test_rewards_split_by_stake() {
+    let mut env = TestEnv::new();
+    let small = env.stake(dec!(100));
+    let large = env.stake(dec!(300));
+
+    env.advance_days(1);
+
+    assert_eq!(env.claim(&small), dec!(25));
+    assert_eq!(env.claim(&large), dec!(75));
+}
+
+#[test]
+fn test_claim_without_new_periods_fails() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    let receipt = env.update_id(&id);
+    expect_failure(&receipt, "Wait longer to claim your rewards.");
+}
+
+#[test]
+fn test_total_claimable_sums_previews() {
+    let mut env = TestEnv::new();
+    let first = env.stake(dec!(100));
+    let second = env.stake(dec!(100));
+    let third = env.stake(dec!(200));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let mut sum = dec!(0);
+    for id in [&first, &second, &third] {
+        let preview: Decimal = env.read("preview_rewards", manifest_args!(id.clone()));
+        sum += preview;
+    }
+    let total: Decimal = env.read(
+        "total_claimable",
+        manifest_args!(vec![first, second, third]),
     );
-    println!("{:?}\n", receipt);
-    receipt.expect_commit_success();
+    assert_eq!(total, sum);
+    assert_eq!(total, REWARD_AMOUNT);
+}
+
+#[test]
+fn test_update_id_capped_claims_oldest_periods() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(3);
+
+    let before = env.balance(env.reward);
+    env.call_with_id(&id, "update_id_capped", |proof| (proof, 1i64))
+        .expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + REWARD_AMOUNT);
+
+    let next_period: i64 = env.read("id_next_period", manifest_args!(id.clone()));
+    assert_eq!(next_period, 2);
+
+    assert_eq!(env.claim(&id), REWARD_AMOUNT * dec!(2));
+}
+
+#[test]
+fn test_eligibility_delay_skips_first_period() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("set_eligibility_delay", manifest_args!(token, 1i64))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), dec!(0));
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), REWARD_AMOUNT);
+}
+
+#[test]
+fn test_reward_window_limits_emission() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("set_reward_window", manifest_args!(token, Some(1i64), Some(1i64)))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), dec!(0));
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), REWARD_AMOUNT);
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), dec!(0));
+}
+
+#[test]
+fn test_emissions_paused_record_nothing() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let id = env.stake(dec!(100));
+    env.owner_call("set_emissions_paused", manifest_args!(token, true))
+        .expect_commit_success();
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), dec!(0));
+}
+
+#[test]
+fn test_global_reward_multiplier_scales_rewards() {
+    let mut env = TestEnv::new();
+    env.owner_call("set_global_reward_multiplier", manifest_args!(dec!(2)))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), REWARD_AMOUNT * dec!(2));
 }
 
 #[test]
-fn test_hello_with_test_environment() -> Result<(), RuntimeError> {
-    // Arrange
-    let mut env = TestEnvironment::new();
-    let package_address = Package::compile_and_publish(this_package!(), &mut env)?;
+fn test_square_root_emission() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("set_emission_curve", manifest_args!(token, EmissionCurve::SquareRoot))
+        .expect_commit_success();
+    env.owner_call("set_rewards", manifest_args!(token, dec!(1)))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
 
-    let mut hello = Hello::instantiate_hello(package_address, &mut env)?;
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), dec!(10));
+}
+
+#[test]
+fn test_round_down_rewards_moves_dust() {
+    let mut env = TestEnv::new();
+    env.owner_call("set_round_down_rewards", manifest_args!(true))
+        .expect_commit_success();
+    env.stake(dec!(3));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let before = env.balance(env.reward);
+    env.owner_call("reclaim_dust", manifest_args!())
+        .expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + dec!("0.000000000001"));
+}
+
+#[test]
+fn test_claim_cooldown() {
+    let mut env = TestEnv::new();
+    env.owner_call("set_claim_cooldown", manifest_args!(2880i64))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    env.update_id(&id).expect_commit_success();
+
+    env.advance_days(1);
+    let receipt = env.update_id(&id);
+    expect_failure(&receipt, "Claim cooldown has not passed yet.");
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), REWARD_AMOUNT * dec!(2));
+}
+
+#[test]
+fn test_max_lifetime_reward_caps_claims() {
+    let mut env = TestEnv::new();
+    env.owner_call("set_max_lifetime_reward", manifest_args!(Some(dec!(150))))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), dec!(100));
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), dec!(50));
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), dec!(0));
+}
 
-    // Act
-    let bucket = hello.free_token(&mut env)?;
+#[test]
+fn test_vesting_rewards() {
+    let mut env = TestEnv::new();
+    env.owner_call("set_vesting_duration", manifest_args!(10i64))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), dec!(0));
+    assert_eq!(env.balance(env.vesting_receipt), dec!(1));
+
+    env.advance_days(5);
+    let vesting_receipt = env.vesting_receipt;
+    let before = env.balance(env.reward);
+    env.call_with_nft(vesting_receipt, &NonFungibleLocalId::integer(1), "claim_vested", |bucket| (bucket,))
+        .expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + dec!(50));
+    assert_eq!(env.balance(env.vesting_receipt), dec!(1));
+
+    env.advance_days(5);
+    env.call_with_nft(vesting_receipt, &NonFungibleLocalId::integer(1), "claim_vested", |bucket| (bucket,))
+        .expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + dec!(100));
+    assert_eq!(env.balance(env.vesting_receipt), dec!(0));
+}
+
+#[test]
+fn test_compound_rewards_stakes_reward_token() {
+    let mut env = TestEnv::new();
+    let reward = env.reward;
+    env.owner_call("add_stakable", manifest_args!(reward, dec!(0), default_lock()))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    env.call_with_id(&id, "compound_rewards", |proof| (proof,))
+        .expect_commit_success();
+
+    assert_eq!(env.staked(&id, reward), REWARD_AMOUNT);
+}
+
+#[test]
+fn test_compound_requires_stakable_reward_token() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    let receipt = env.call_with_id(&id, "compound_rewards", |proof| (proof,));
+    expect_failure(&receipt, "Reward token is not stakable.");
+}
+
+#[test]
+fn test_claim_history_and_staked_history() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let id = env.stake(dec!(100));
 
-    // Assert
-    let amount = bucket.amount(&mut env)?;
-    assert_eq!(amount, dec!("1"));
+    env.advance_days(1);
+    env.update_id(&id).expect_commit_success();
+
+    let claimed: Option<Decimal> = env.read("claim_history", manifest_args!(id.clone(), 0i64));
+    assert_eq!(claimed, Some(REWARD_AMOUNT));
+    let staked: Option<Decimal> = env.read("historical_staked", manifest_args!(token, 0i64));
+    assert_eq!(staked, Some(dec!(100)));
+}
+
+#[test]
+fn test_sweep_claim_requires_opt_in() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+    env.advance_days(1);
+
+    let receipt = env.owner_call("sweep_claim", manifest_args!(id.clone()));
+    expect_failure(&receipt, "Staking ID has not opted in to sweeping.");
+
+    env.call_with_id(&id, "set_sweepable", |proof| (proof, true))
+        .expect_commit_success();
+    let before = env.balance(env.reward);
+    env.owner_call("sweep_claim", manifest_args!(id))
+        .expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + REWARD_AMOUNT);
+}
+
+#[test]
+fn test_reducing_max_claim_delay_forfeits_old_periods() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(4);
+    env.update_period().expect_commit_success();
+
+    env.owner_call("set_max_claim_delay", manifest_args!(1i64))
+        .expect_commit_success();
+    let before = env.balance(env.reward);
+    env.owner_call("collect_forfeited", manifest_args!())
+        .expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + REWARD_AMOUNT * dec!(3));
+
+    assert_eq!(env.claim(&id), REWARD_AMOUNT);
+}
+
+#[test]
+fn test_remove_rewards_keeps_owed_rewards() {
+    let mut env = TestEnv::new();
+    env.stake(dec!(100));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let receipt = env.owner_call("remove_rewards", manifest_args!(INITIAL_REWARDS - dec!(50)));
+    expect_failure(&receipt, "Cannot remove rewards owed to stakers.");
+    env.owner_call("remove_rewards", manifest_args!(INITIAL_REWARDS - REWARD_AMOUNT))
+        .expect_commit_success();
+}
+
+#[test]
+fn test_runway_and_health() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+
+    let runway: i64 = env.read("stakable_runway", manifest_args!(token));
+    assert_eq!(runway, 100);
+    let healthy: bool = env.read("is_healthy", manifest_args!());
+    assert!(healthy);
+
+    env.owner_call("remove_rewards", manifest_args!(INITIAL_REWARDS - dec!(50)))
+        .expect_commit_success();
+    let healthy: bool = env.read("is_healthy", manifest_args!());
+    assert!(!healthy);
+}
+
+#[test]
+fn test_configure_rewards_funds_and_sets_rate() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+
+    env.owner_call_with_bucket(reward, dec!(500), "configure_rewards", |bucket| {
+        (token, bucket, dec!(200))
+    })
+    .expect_commit_success();
+
+    let (_, available): (ResourceAddress, Decimal) = env.read("reward_info", manifest_args!());
+    assert_eq!(available, INITIAL_REWARDS + dec!(500));
+    let rates: Vec<(ResourceAddress, Decimal)> = env.read("all_reward_rates", manifest_args!());
+    assert_eq!(rates, vec![(token, dec!(200))]);
+
+    let receipt = env.owner_call_with_bucket(token, dec!(10), "configure_rewards", |bucket| {
+        (token, bucket, dec!(200))
+    });
+    expect_failure(&receipt, "Funding must be the reward token.");
+}
+
+#[test]
+fn test_total_distributed_matches_claims() {
+    let mut env = TestEnv::new();
+    let first = env.stake(dec!(100));
+    let second = env.stake(dec!(300));
+
+    env.advance_days(1);
+    let mut claimed = env.claim(&first);
+    env.advance_days(2);
+    claimed += env.claim(&first);
+    claimed += env.claim(&second);
+
+    assert_eq!(claimed, dec!(300));
+    let distributed: Decimal = env.read("total_distributed", manifest_args!());
+    assert_eq!(distributed, claimed);
+}
+
+#[test]
+fn test_claims_cover_every_completed_period_once() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+    let mut next_period: i64 = 1;
+
+    for gap in 1..5i64 {
+        env.advance_days(gap);
+        let claimed = env.claim(&id);
+
+        let current_period = next_period - 1 + gap;
+        assert_eq!(claimed, REWARD_AMOUNT * Decimal::from(gap));
+        for period in (next_period - 1)..current_period {
+            let history: Option<Decimal> = env.read("claim_history", manifest_args!(id.clone(), period));
+            assert_eq!(history, Some(REWARD_AMOUNT));
+        }
+        let history: Option<Decimal> =
+            env.read("claim_history", manifest_args!(id.clone(), current_period));
+        assert_eq!(history, None);
+
+        next_period = env.read("id_next_period", manifest_args!(id.clone()));
+        assert_eq!(next_period, current_period + 1);
+    }
+}
+
+#[test]
+fn test_all_reward_rates_lists_every_stakable() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let account = env.account;
+    let second = env.runner.create_fungible_resource(dec!(1000), 18, account);
+    let third = env.runner.create_fungible_resource(dec!(1000), 18, account);
+    env.owner_call("add_stakable", manifest_args!(second, dec!(50), default_lock()))
+        .expect_commit_success();
+    env.owner_call("add_stakable", manifest_args!(third, dec!(200), default_lock()))
+        .expect_commit_success();
+
+    let mut rates: Vec<(ResourceAddress, Decimal)> = env.read("all_reward_rates", manifest_args!());
+    rates.sort();
+    let mut expected = vec![(token, REWARD_AMOUNT), (second, dec!(50)), (third, dec!(200))];
+    expected.sort();
+    assert_eq!(rates, expected);
+}
+
+#[test]
+fn test_current_reward_rate_matches_recorded_rate() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+
+    let rate: Decimal = env.read("current_reward_rate", manifest_args!(token));
+    assert_eq!(rate, dec!(0));
+
+    let first = env.stake(dec!(100));
+    env.stake(dec!(300));
+    let rate: Decimal = env.read("current_reward_rate", manifest_args!(token));
+    assert_eq!(rate, dec!("0.25"));
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&first), rate * dec!(100));
+}
+
+#[test]
+fn test_update_id_and_swap_through_dex() {
+    let mut env = TestEnv::new();
+    let account = env.account;
+    let target = env.runner.create_fungible_resource(dec!(1000), 18, account);
+    let dex_package = env
+        .runner
+        .compile_and_publish(format!("{}/tests/mock_dex", this_package!()));
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_from_account(account, target, dec!(500))
+        .take_all_from_worktop(target, "output")
+        .call_function_with_name_lookup(dex_package, "MockDex", "instantiate", |lookup| {
+            (lookup.bucket("output"), dec!(2))
+        })
+        .build();
+    let dex = env.execute(manifest).expect_commit_success().new_component_addresses()[0];
+
+    let id = env.stake(dec!(100));
+    env.advance_days(1);
+
+    let receipt = env.call_with_id(&id, "update_id_and_swap", |proof| {
+        (proof, dex, REWARD_AMOUNT * dec!(2) + dec!(1), target)
+    });
+    expect_failure(&receipt, "Swap output below minimum.");
+
+    let before = env.balance(target);
+    let rewards_before = env.balance(env.reward);
+    env.call_with_id(&id, "update_id_and_swap", |proof| {
+        (proof, dex, REWARD_AMOUNT * dec!(2), target)
+    })
+    .expect_commit_success();
+    assert_eq!(env.balance(target), before + REWARD_AMOUNT * dec!(2));
+    assert_eq!(env.balance(env.reward), rewards_before);
+}
+
+//////////////////////////////////////////////////////////////////////
+////////////////////////////////UNSTAKING/////////////////////////////
+//////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_unstake_redeemable_after_delay() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let id = env.stake(dec!(100));
+
+    env.start_unstake(&id, dec!(40), false).expect_commit_success();
+    let receipt_id = env.last_unstake_receipt();
+    assert_eq!(env.staked(&id, token), dec!(60));
+    let unstaking: Decimal = env.read("unstaking_total", manifest_args!(token));
+    assert_eq!(unstaking, dec!(40));
+
+    let receipt = env.finish_unstake(&receipt_id);
+    expect_failure(&receipt, "You cannot unstake tokens before the redemption time.");
+
+    env.advance_days(7);
+    let before = env.balance(token);
+    env.finish_unstake(&receipt_id).expect_commit_success();
+    assert_eq!(env.balance(token), before + dec!(40));
+    let unstaking: Decimal = env.read("unstaking_total", manifest_args!(token));
+    assert_eq!(unstaking, dec!(0));
+}
+
+#[test]
+fn test_next_receipt_ids_predicts_minted_id() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let id = env.stake(dec!(100));
+
+    let (next_unstake, next_transfer): (u64, u64) = env.read("next_receipt_ids", manifest_args!());
+    assert_eq!(next_transfer, 1);
+
+    env.start_unstake(&id, dec!(30), false).expect_commit_success();
+
+    let (address, amount): (ResourceAddress, Decimal) = env.read(
+        "receipt_info",
+        manifest_args!(NonFungibleLocalId::integer(next_unstake), true),
+    );
+    assert_eq!(address, token);
+    assert_eq!(amount, dec!(30));
+    assert_eq!(env.last_unstake_receipt(), NonFungibleLocalId::integer(next_unstake));
+}
+
+#[test]
+fn test_unstake_delay_cannot_exceed_maximum() {
+    let mut env = TestEnv::new();
+
+    let receipt = env.owner_call("set_unstake_delay", manifest_args!(31i64));
+    expect_failure(&receipt, "Unstaking delay cannot be longer than the maximum unstaking delay.");
+    env.owner_call("set_unstake_delay", manifest_args!(30i64))
+        .expect_commit_success();
+}
+
+#[test]
+fn test_accelerate_unstake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let id = env.stake(dec!(100));
+
+    env.start_unstake(&id, dec!(100), false).expect_commit_success();
+    let receipt_id = env.last_unstake_receipt();
+
+    env.owner_call("set_unstake_delay", manifest_args!(1i64))
+        .expect_commit_success();
+    env.owner_call("accelerate_unstake", manifest_args!(receipt_id.clone()))
+        .expect_commit_success();
+
+    env.advance_days(1);
+    let before = env.balance(token);
+    env.finish_unstake(&receipt_id).expect_commit_success();
+    assert_eq!(env.balance(token), before + dec!(100));
+}
+
+#[test]
+fn test_max_outstanding_unstakes() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+    env.owner_call("set_max_outstanding_unstakes", manifest_args!(1u64))
+        .expect_commit_success();
+
+    env.start_unstake(&id, dec!(10), false).expect_commit_success();
+    let first = env.last_unstake_receipt();
+    let receipt = env.start_unstake(&id, dec!(10), false);
+    expect_failure(&receipt, "Too many outstanding unstake receipts, finish an unstake first.");
+
+    env.advance_days(7);
+    env.finish_unstake(&first).expect_commit_success();
+    env.start_unstake(&id, dec!(10), false).expect_commit_success();
+}
+
+#[test]
+fn test_restake_receipt_returns_stake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let unstake_receipt = env.unstake_receipt;
+    let id = env.stake(dec!(100));
+
+    env.start_unstake(&id, dec!(40), false).expect_commit_success();
+    let receipt_id = env.last_unstake_receipt();
+
+    let builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account, unstake_receipt, vec![receipt_id])
+        .take_all_from_worktop(unstake_receipt, "receipt")
+        .create_proof_from_account_of_non_fungibles(env.account, env.id_resource, vec![id.clone()])
+        .pop_from_auth_zone("id_proof")
+        .call_method_with_name_lookup(env.component, "restake_receipt", |lookup| {
+            (lookup.bucket("receipt"), lookup.proof("id_proof"), token)
+        });
+    let manifest = env.deposit(builder).build();
+    env.execute(manifest).expect_commit_success();
+
+    assert_eq!(env.staked(&id, token), dec!(100));
+}
+
+#[test]
+fn test_unstake_receipts_info_reads_several_receipts() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let id = env.stake(dec!(100));
+
+    env.start_unstake(&id, dec!(10), false).expect_commit_success();
+    let first = env.last_unstake_receipt();
+    env.advance_days(1);
+    env.start_unstake(&id, dec!(20), false).expect_commit_success();
+    let second = env.last_unstake_receipt();
+
+    let info: Vec<(NonFungibleLocalId, ResourceAddress, Decimal, Instant)> = env.read(
+        "unstake_receipts_info",
+        manifest_args!(vec![first.clone(), second.clone()]),
+    );
+    let first_redemption: Instant = env.read("unstake_redemption_time", manifest_args!(first.clone()));
+    let second_redemption: Instant = env.read("unstake_redemption_time", manifest_args!(second.clone()));
+    assert_eq!(
+        info,
+        vec![
+            (first, token, dec!(10), first_redemption),
+            (second, token, dec!(20), second_redemption),
+        ]
+    );
+    assert_eq!(
+        second_redemption.seconds_since_unix_epoch - first_redemption.seconds_since_unix_epoch,
+        86400
+    );
+
+    let too_many: Vec<NonFungibleLocalId> = (1..102).map(NonFungibleLocalId::integer).collect();
+    let receipt = env.call("unstake_receipts_info", manifest_args!(too_many));
+    expect_failure(&receipt, "Too many receipts requested.");
+}
+
+//////////////////////////////////////////////////////////////////////
+/////////////////////////////STAKE TRANSFERS//////////////////////////
+//////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_stake_transfer_receipt_moves_stake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let transfer_receipt = env.transfer_receipt;
+    let sender = env.stake(dec!(100));
+
+    env.start_unstake(&sender, dec!(40), true).expect_commit_success();
+    let receipt_id = env.last_transfer_receipt();
+    assert_eq!(env.staked(&sender, token), dec!(60));
+
+    env.call_with_nft(transfer_receipt, &receipt_id, "stake", |bucket| {
+        (bucket, None::<ManifestProof>, None::<ManifestProof>)
+    })
+    .expect_commit_success();
+    env.ids += 1;
+    let receiver = NonFungibleLocalId::integer(env.ids);
+
+    assert_eq!(env.staked(&receiver, token), dec!(40));
+}
+
+#[test]
+fn test_transfer_whitelist() {
+    let mut env = TestEnv::new();
+    let transfer_receipt = env.transfer_receipt;
+    let other_badge = env.other_badge;
+    let sender = env.stake(dec!(100));
+    env.owner_call("set_transfer_whitelist", manifest_args!(Some(other_badge)))
+        .expect_commit_success();
+
+    env.start_unstake(&sender, dec!(40), true).expect_commit_success();
+    let receipt_id = env.last_transfer_receipt();
+
+    let receipt = env.call_with_nft(transfer_receipt, &receipt_id, "stake", |bucket| {
+        (bucket, None::<ManifestProof>, None::<ManifestProof>)
+    });
+    expect_failure(&receipt, "Whitelist badge required to redeem a stake transfer receipt.");
+
+    let builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account, transfer_receipt, vec![receipt_id])
+        .take_all_from_worktop(transfer_receipt, "receipt")
+        .create_proof_from_account_of_amount(env.account, other_badge, dec!(1))
+        .pop_from_auth_zone("whitelist_proof")
+        .call_method_with_name_lookup(env.component, "stake", |lookup| {
+            (lookup.bucket("receipt"), None::<ManifestProof>, Some(lookup.proof("whitelist_proof")))
+        });
+    let manifest = env.deposit(builder).build();
+    env.execute(manifest).expect_commit_success();
+}
+
+#[test]
+fn test_transfer_requires_claimed_rewards() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let receipt = env.start_unstake(&id, dec!(40), true);
+    expect_failure(&receipt, "Please claim unclaimed rewards on your ID before transferring stake.");
+}
+
+#[test]
+fn test_transfer_receipt_to_unstake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let transfer_receipt = env.transfer_receipt;
+    let id = env.stake(dec!(100));
+
+    env.start_unstake(&id, dec!(40), true).expect_commit_success();
+    let receipt_id = env.last_transfer_receipt();
+    env.call_with_nft(transfer_receipt, &receipt_id, "transfer_receipt_to_unstake", |bucket| (bucket,))
+        .expect_commit_success();
+    let unstake_id = env.last_unstake_receipt();
+
+    env.advance_days(7);
+    let before = env.balance(token);
+    env.finish_unstake(&unstake_id).expect_commit_success();
+    assert_eq!(env.balance(token), before + dec!(40));
+}
+
+#[test]
+fn test_finish_unstake_rejects_transfer_receipt() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let transfer_receipt = env.transfer_receipt;
+    let id = env.stake(dec!(100));
+
+    env.start_unstake(&id, dec!(40), true).expect_commit_success();
+    let transfer_id = env.last_transfer_receipt();
+    env.advance_days(7);
+    let receipt = env.call_with_nft(transfer_receipt, &transfer_id, "finish_unstake", |bucket| (bucket,));
+    expect_failure(&receipt, "Invalid unstake receipt supplied!");
+
+    env.start_unstake(&id, dec!(60), false).expect_commit_success();
+    let unstake_id = env.last_unstake_receipt();
+    env.advance_days(7);
+    let before = env.balance(token);
+    env.finish_unstake(&unstake_id).expect_commit_success();
+    assert_eq!(env.balance(token), before + dec!(60));
+}
+
+//////////////////////////////////////////////////////////////////////
+//////////////////////////////////LOCKING/////////////////////////////
+//////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_lock_stake_pays_lock_reward_and_blocks_unstake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    let before = env.balance(reward);
+    env.call_with_id(&id, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+    assert_eq!(env.balance(reward), before + dec!(100));
+
+    let has_locks: bool = env.read("has_active_locks", manifest_args!(id.clone()));
+    assert!(has_locks);
+
+    let receipt = env.start_unstake(&id, dec!(10), false);
+    expect_failure(&receipt, "You cannot unstake tokens currently participating in a vote.");
+
+    let receipt = env.call_with_id(&id, "lock_stake", |proof| (token, proof));
+    expect_failure(&receipt, "Tokens are already locked.");
+}
+
+#[test]
+fn test_locked_stake_can_be_queued_for_unstake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+    env.call_with_id(&id, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    env.call_with_id(&id, "start_unstake", |proof| (proof, token, dec!(100), false, false, true))
+        .expect_commit_success();
+    let receipt_id = env.last_unstake_receipt();
+
+    env.advance_days(7);
+    let receipt = env.finish_unstake(&receipt_id);
+    expect_failure(&receipt, "You cannot unstake tokens before the redemption time.");
+
+    env.advance_days(23);
+    env.finish_unstake(&receipt_id).expect_commit_success();
+}
+
+#[test]
+fn test_queued_locked_stake_leaves_lock() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+    env.call_with_id(&id, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    env.call_with_id(&id, "start_unstake", |proof| (proof, token, dec!(40), false, false, true))
+        .expect_commit_success();
+    env.stake_to(&id, dec!(40)).expect_commit_success();
+
+    env.start_unstake(&id, dec!(40), false).expect_commit_success();
+    let receipt = env.start_unstake(&id, dec!(1), false);
+    expect_failure(&receipt, "You cannot unstake tokens currently participating in a vote.");
+}
+
+#[test]
+fn test_accelerated_queued_unstake_waits_for_lock() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+    env.call_with_id(&id, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    env.call_with_id(&id, "start_unstake", |proof| (proof, token, dec!(100), false, false, true))
+        .expect_commit_success();
+    let receipt_id = env.last_unstake_receipt();
+
+    env.owner_call("set_unstake_delay", manifest_args!(1i64))
+        .expect_commit_success();
+    env.owner_call("accelerate_unstake", manifest_args!(receipt_id.clone()))
+        .expect_commit_success();
+
+    env.advance_days(7);
+    let receipt = env.finish_unstake(&receipt_id);
+    expect_failure(&receipt, "You cannot unstake tokens before the redemption time.");
+
+    env.advance_days(23);
+    env.finish_unstake(&receipt_id).expect_commit_success();
+}
+
+#[test]
+fn test_set_lock_requires_dao_control() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let id = env.stake(dec!(100));
+
+    let now = env.runner.get_current_proposer_timestamp_ms() / 1000;
+    let receipt = env.owner_call(
+        "set_lock",
+        manifest_args!(token, Instant::new(now + 86400), id),
+    );
+    expect_failure(
+        &receipt,
+        "This functionality is only available if a DAO is controlling the staking.",
+    );
+}
+
+#[test]
+fn test_set_lock_bounds_lock_duration() {
+    let mut env = TestEnv::with(Setup {
+        dao_controlled: true,
+        ..Setup::default()
+    });
+    let token = env.token;
+    let id = env.stake(dec!(100));
+
+    let now = env.runner.get_current_proposer_timestamp_ms() / 1000;
+    let receipt = env.owner_call(
+        "set_lock",
+        manifest_args!(token, Instant::new(now + 366 * 86400), id.clone()),
+    );
+    expect_failure(&receipt, "Lock exceeds the maximum lock duration.");
+
+    env.owner_call(
+        "set_lock",
+        manifest_args!(token, Instant::new(now + 86400), id.clone()),
+    )
+    .expect_commit_success();
+    let receipt = env.start_unstake(&id, dec!(10), false);
+    expect_failure(&receipt, "You cannot unstake tokens currently participating in a vote.");
+}
+
+#[test]
+fn test_emergency_unstake_disabled_by_default() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let id = env.stake(dec!(100));
+
+    let receipt = env.call_with_id(&id, "emergency_unstake", |proof| (proof, token, dec!(50)));
+    expect_failure(&receipt, "Emergency unstaking is disabled.");
+}
+
+#[test]
+fn test_emergency_unstake_charges_penalty_once_enabled() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let id = env.stake(dec!(100));
+    env.owner_call("set_emergency_penalty", manifest_args!(Some(dec!("0.1"))))
+        .expect_commit_success();
+
+    let before = env.balance(token);
+    env.call_with_id(&id, "emergency_unstake", |proof| (proof, token, dec!(50)))
+        .expect_commit_success();
+    assert_eq!(env.balance(token), before + dec!(45));
+    assert_eq!(env.staked(&id, token), dec!(50));
+
+    env.owner_call("collect_penalties", manifest_args!(token))
+        .expect_commit_success();
+    assert_eq!(env.balance(token), before + dec!(50));
+
+    env.owner_call("set_emergency_penalty", manifest_args!(None::<Decimal>))
+        .expect_commit_success();
+    let receipt = env.call_with_id(&id, "emergency_unstake", |proof| (proof, token, dec!(10)));
+    expect_failure(&receipt, "Emergency unstaking is disabled.");
+}
+
+#[test]
+fn test_unstake_penalty_rounded_to_divisibility() {
+    let mut env = TestEnv::new();
+    let account = env.account;
+    let coarse = env.runner.create_fungible_resource(dec!(1000), 2, account);
+    env.owner_call("add_stakable", manifest_args!(coarse, REWARD_AMOUNT, default_lock()))
+        .expect_commit_success();
+    env.owner_call("set_unstake_penalty", manifest_args!(dec!("0.333")))
+        .expect_commit_success();
+    let id = env.stake_new_id(coarse, dec!(10));
+
+    env.call_with_id(&id, "start_unstake", |proof| (proof, coarse, dec!(1), false, false, false))
+        .expect_commit_success();
+    let receipt_id = env.last_unstake_receipt();
+    let (_, amount): (ResourceAddress, Decimal) =
+        env.read("receipt_info", manifest_args!(receipt_id, true));
+    assert_eq!(amount, dec!("0.67"));
+
+    let before = env.balance(coarse);
+    env.owner_call("collect_penalties", manifest_args!(coarse))
+        .expect_commit_success();
+    assert_eq!(env.balance(coarse), before + dec!("0.33"));
+}
+
+#[test]
+fn test_emergency_penalty_rounded_to_divisibility() {
+    let mut env = TestEnv::new();
+    let account = env.account;
+    let whole = env.runner.create_fungible_resource(dec!(1000), 0, account);
+    env.owner_call("add_stakable", manifest_args!(whole, REWARD_AMOUNT, default_lock()))
+        .expect_commit_success();
+    env.owner_call("set_emergency_penalty", manifest_args!(Some(dec!("0.25"))))
+        .expect_commit_success();
+    let id = env.stake_new_id(whole, dec!(10));
+
+    let before = env.balance(whole);
+    env.call_with_id(&id, "emergency_unstake", |proof| (proof, whole, dec!(10)))
+        .expect_commit_success();
+    assert_eq!(env.balance(whole), before + dec!(8));
+}
+
+#[test]
+fn test_unstake_penalty_waived_after_reward_cut() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("set_unstake_penalty", manifest_args!(dec!("0.5")))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+    env.owner_call("set_rewards", manifest_args!(token, dec!(50)))
+        .expect_commit_success();
+
+    env.start_unstake(&id, dec!(10), false).expect_commit_success();
+    let receipt_id = env.last_unstake_receipt();
+    let (_, amount): (ResourceAddress, Decimal) =
+        env.read("receipt_info", manifest_args!(receipt_id, true));
+    assert_eq!(amount, dec!(10));
+
+    env.advance_days(7);
+    env.start_unstake(&id, dec!(10), false).expect_commit_success();
+    let receipt_id = env.last_unstake_receipt();
+    let (_, amount): (ResourceAddress, Decimal) =
+        env.read("receipt_info", manifest_args!(receipt_id, true));
+    assert_eq!(amount, dec!(5));
+}
+
+#[test]
+fn test_stakable_without_lock() {
+    let mut env = TestEnv::new();
+    let account = env.account;
+    let unlockable = env.runner.create_fungible_resource(dec!(1000), 18, account);
+
+    let paid_lock = Lock {
+        payment: dec!(1),
+        duration: 0,
+        multiplier: dec!(1),
+    };
+    let receipt = env.owner_call("add_stakable", manifest_args!(unlockable, REWARD_AMOUNT, paid_lock));
+    expect_failure(&receipt, "A stakable that is not lockable cannot have a lock payment or multiplier.");
+
+    let no_lock = Lock {
+        payment: dec!(0),
+        duration: 0,
+        multiplier: dec!(1),
+    };
+    env.owner_call("add_stakable", manifest_args!(unlockable, REWARD_AMOUNT, no_lock))
+        .expect_commit_success();
+    let id = env.stake_new_id(unlockable, dec!(10));
+
+    let receipt = env.call_with_id(&id, "lock_stake", |proof| (unlockable, proof));
+    expect_failure(&receipt, "Stakable is not lockable.");
+}
+
+#[test]
+fn test_lock_duration_bounds() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+
+    let too_long = Lock {
+        duration: 366,
+        ..default_lock()
+    };
+    let receipt = env.owner_call("edit_stakable", manifest_args!(token, REWARD_AMOUNT, too_long));
+    expect_failure(&receipt, "Lock duration is longer than the maximum lock duration.");
+
+    let receipt = env.owner_call("set_min_lock_duration", manifest_args!(366i64));
+    expect_failure(&receipt, "Minimum lock duration cannot be longer than the maximum lock duration.");
+
+    env.owner_call("set_min_lock_duration", manifest_args!(31i64))
+        .expect_commit_success();
+    let receipt = env.owner_call("edit_stakable", manifest_args!(token, REWARD_AMOUNT, default_lock()));
+    expect_failure(&receipt, "Lock duration is shorter than the minimum lock duration.");
+}
+
+#[test]
+fn test_restake_receipt_requires_active_stakable() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let unstake_receipt = env.unstake_receipt;
+    let id = env.stake(dec!(100));
+
+    env.start_unstake(&id, dec!(40), false).expect_commit_success();
+    let receipt_id = env.last_unstake_receipt();
+    env.owner_call("set_stakable_active", manifest_args!(token, false))
+        .expect_commit_success();
+
+    let builder = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .withdraw_non_fungibles_from_account(env.account, unstake_receipt, vec![receipt_id.clone()])
+        .take_all_from_worktop(unstake_receipt, "receipt")
+        .create_proof_from_account_of_non_fungibles(env.account, env.id_resource, vec![id.clone()])
+        .pop_from_auth_zone("id_proof")
+        .call_method_with_name_lookup(env.component, "restake_receipt", |lookup| {
+            (lookup.bucket("receipt"), lookup.proof("id_proof"), token)
+        });
+    let manifest = env.deposit(builder).build();
+    let receipt = env.execute(manifest);
+    expect_failure(&receipt, "Token is not stakable.");
+
+    env.advance_days(7);
+    env.finish_unstake(&receipt_id).expect_commit_success();
+}
+
+#[test]
+fn test_owner_rule_replaces_controller_everywhere() {
+    let mut env = TestEnv::with(Setup {
+        owned_by_other_badge: true,
+        ..Setup::default()
+    });
+    let controller = env.controller;
+    let id_resource = env.id_resource;
+
+    env.owner_call("set_keeper_reward", manifest_args!(dec!(1)))
+        .expect_commit_success();
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(env.account, controller, dec!(1))
+        .call_method(env.component, "set_keeper_reward", manifest_args!(dec!(2)))
+        .build();
+    env.execute(manifest).expect_commit_failure();
+
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(env.account, controller, dec!(1))
+        .set_metadata(id_resource, "name", MetadataValue::String("Controller ID".to_string()))
+        .build();
+    env.execute(manifest).expect_commit_failure();
+
+    let owner_badge = env.owner_badge;
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(env.account, owner_badge, dec!(1))
+        .set_metadata(id_resource, "name", MetadataValue::String("Owner ID".to_string()))
+        .build();
+    env.execute(manifest).expect_commit_success();
+}
+
+#[test]
+fn test_set_next_period_to_now_after_interval_change() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.stake(dec!(100));
+    env.owner_call("set_period_interval", manifest_args!(7i64))
+        .expect_commit_success();
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+    env.owner_call("set_period_interval", manifest_args!(1i64))
+        .expect_commit_success();
+
+    env.advance_minutes(60);
+    env.owner_call("set_next_period_to_now", manifest_args!())
+        .expect_commit_success();
+    let recorded: Option<Decimal> = env.read("historical_staked", manifest_args!(token, 1i64));
+    assert_eq!(recorded, Some(dec!(100)));
+    let seconds: i64 = env.read("seconds_to_next_period", manifest_args!());
+    assert_eq!(seconds, 86400);
+
+    let receipt = env.owner_call("set_next_period_to_now", manifest_args!());
+    expect_failure(&receipt, "Current period has only just started.");
+
+    env.advance_minutes(1);
+    env.owner_call("set_next_period_to_now", manifest_args!())
+        .expect_commit_success();
+    let recorded: Option<Decimal> = env.read("historical_staked", manifest_args!(token, 2i64));
+    assert_eq!(recorded, Some(dec!(100)));
+}
+
+#[test]
+fn test_raising_max_claim_delay_keeps_released_periods_unclaimable() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(8);
+    env.update_period().expect_commit_success();
+    env.owner_call("set_max_claim_delay", manifest_args!(10i64))
+        .expect_commit_success();
+
+    let preview: Decimal = env.read("preview_rewards", manifest_args!(id.clone()));
+    assert_eq!(preview, REWARD_AMOUNT * dec!(5));
+    assert_eq!(env.claim(&id), REWARD_AMOUNT * dec!(5));
+
+    let receipt = env.owner_call("remove_rewards", manifest_args!(INITIAL_REWARDS - REWARD_AMOUNT * dec!(5)));
+    receipt.expect_commit_success();
+}
+
+#[test]
+fn test_retire_reward_token_without_obligations() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    let before = env.balance(env.reward);
+    env.owner_call("retire_reward_token", manifest_args!())
+        .expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + INITIAL_REWARDS);
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&id), dec!(0));
+}
+
+#[test]
+fn test_retire_reward_token_with_outstanding_obligations() {
+    let mut env = TestEnv::new();
+    let first = env.stake(dec!(100));
+    let second = env.stake(dec!(100));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let receipt = env.owner_call("retire_reward_token", manifest_args!());
+    expect_failure(&receipt, "Unclaimed rewards are still owed to stakers.");
+
+    assert_eq!(env.claim(&first), dec!(50));
+    let receipt = env.owner_call("retire_reward_token", manifest_args!());
+    expect_failure(&receipt, "Unclaimed rewards are still owed to stakers.");
+
+    assert_eq!(env.claim(&second), dec!(50));
+    let before = env.balance(env.reward);
+    env.owner_call("retire_reward_token", manifest_args!())
+        .expect_commit_success();
+    assert_eq!(env.balance(env.reward), before + INITIAL_REWARDS - REWARD_AMOUNT);
+}
+
+#[test]
+fn test_preview_follows_claim_rules() {
+    let mut env = TestEnv::new();
+    env.owner_call("set_claim_cooldown", manifest_args!(2880i64))
+        .expect_commit_success();
+    env.owner_call("set_max_lifetime_reward", manifest_args!(Some(dec!(60))))
+        .expect_commit_success();
+    let first = env.stake(dec!(100));
+    let second = env.stake(dec!(100));
+    let third = env.stake(dec!(200));
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&first), dec!(25));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let preview: Decimal = env.read("preview_rewards", manifest_args!(first.clone()));
+    assert_eq!(preview, dec!(0));
+    let preview: Decimal = env.read("preview_rewards", manifest_args!(second.clone()));
+    assert_eq!(preview, dec!(50));
+    let preview: Decimal = env.read("preview_rewards", manifest_args!(third.clone()));
+    assert_eq!(preview, dec!(60));
+
+    let total: Decimal = env.read(
+        "total_claimable",
+        manifest_args!(vec![first, second.clone(), third.clone()]),
+    );
+    assert_eq!(total, dec!(110));
+
+    assert_eq!(env.claim(&second), dec!(50));
+    assert_eq!(env.claim(&third), dec!(60));
+}
+
+// Locks for two days with a reward multiplier of 2, covering the current and the next period.
+fn double_weight_lock() -> Lock {
+    Lock {
+        payment: dec!(0),
+        duration: 2,
+        multiplier: dec!(2),
+    }
+}
+
+#[test]
+fn test_lock_multiplier_weights_reward_base() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("edit_stakable", manifest_args!(token, REWARD_AMOUNT, double_weight_lock()))
+        .expect_commit_success();
+    let locked = env.stake(dec!(100));
+    let unlocked = env.stake(dec!(200));
+    env.call_with_id(&locked, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    env.advance_days(1);
+
+    assert_eq!(env.claim(&locked), dec!(50));
+    assert_eq!(env.claim(&unlocked), dec!(50));
+}
+
+#[test]
+fn test_lock_multiplier_ends_with_last_covered_period() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("edit_stakable", manifest_args!(token, REWARD_AMOUNT, double_weight_lock()))
+        .expect_commit_success();
+    let locked = env.stake(dec!(100));
+    let unlocked = env.stake(dec!(300));
+    env.call_with_id(&locked, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    for _ in 0..3 {
+        env.advance_days(1);
+        env.update_period().expect_commit_success();
+    }
+
+    let total_locked: Decimal = env.read("total_locked", manifest_args!(token));
+    assert_eq!(total_locked, dec!(0));
+
+    assert_eq!(env.claim(&locked), dec!(105));
+    assert_eq!(env.claim(&unlocked), dec!(195));
+}
+
+#[test]
+fn test_lock_participation_counts_locked_share() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    let locked = env.stake(dec!(100));
+    env.stake(dec!(300));
+
+    let participation: Decimal = env.read("lock_participation", manifest_args!(token));
+    assert_eq!(participation, dec!(0));
+
+    env.call_with_id(&locked, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+    let participation: Decimal = env.read("lock_participation", manifest_args!(token));
+    assert_eq!(participation, dec!("0.25"));
+    let total_locked: Decimal = env.read("total_locked", manifest_args!(token));
+    assert_eq!(total_locked, dec!(100));
+}
+
+#[test]
+fn test_lock_participation_includes_dao_locks() {
+    let mut env = TestEnv::with(Setup {
+        dao_controlled: true,
+        ..Setup::default()
+    });
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    let locked = env.stake(dec!(100));
+    let dao_locked = env.stake(dec!(100));
+    env.stake(dec!(200));
+
+    let now = env.runner.get_current_proposer_timestamp_ms() / 1000;
+    env.owner_call(
+        "set_lock",
+        manifest_args!(token, Instant::new(now + 60 * 86400), dao_locked.clone()),
+    )
+    .expect_commit_success();
+    let participation: Decimal = env.read("lock_participation", manifest_args!(token));
+    assert_eq!(participation, dec!("0.25"));
+
+    env.call_with_id(&locked, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+    env.owner_call(
+        "set_lock",
+        manifest_args!(token, Instant::new(now + 10 * 86400), locked.clone()),
+    )
+    .expect_commit_success();
+    let participation: Decimal = env.read("lock_participation", manifest_args!(token));
+    assert_eq!(participation, dec!("0.5"));
+    let total_locked: Decimal = env.read("total_locked", manifest_args!(token));
+    assert_eq!(total_locked, dec!(200));
+
+    env.advance_days(31);
+    env.update_period().expect_commit_success();
+    let total_locked: Decimal = env.read("total_locked", manifest_args!(token));
+    assert_eq!(total_locked, dec!(100));
+
+    env.advance_days(30);
+    env.update_period().expect_commit_success();
+    let participation: Decimal = env.read("lock_participation", manifest_args!(token));
+    assert_eq!(participation, dec!(0));
+}
+
+#[test]
+fn test_lock_stake_requires_claimed_rewards() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let receipt = env.call_with_id(&id, "lock_stake", |proof| (token, proof));
+    expect_failure(&receipt, "Please claim unclaimed rewards on your ID before locking.");
+
+    env.claim(&id);
+    env.call_with_id(&id, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+}
+
+#[test]
+fn test_requiring_lock_for_rewards_only_affects_later_periods() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("edit_stakable", manifest_args!(token, REWARD_AMOUNT, double_weight_lock()))
+        .expect_commit_success();
+    let locked = env.stake(dec!(100));
+    let unlocked = env.stake(dec!(200));
+    env.call_with_id(&locked, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+    env.owner_call("set_require_lock_for_rewards", manifest_args!(token, true))
+        .expect_commit_success();
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    assert_eq!(env.claim(&locked), dec!(150));
+    assert_eq!(env.claim(&unlocked), dec!(50));
+}
+
+#[test]
+fn test_no_longer_requiring_lock_for_rewards_only_affects_later_periods() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("edit_stakable", manifest_args!(token, REWARD_AMOUNT, double_weight_lock()))
+        .expect_commit_success();
+    env.owner_call("set_require_lock_for_rewards", manifest_args!(token, true))
+        .expect_commit_success();
+    let locked = env.stake(dec!(100));
+    let unlocked = env.stake(dec!(200));
+    env.call_with_id(&locked, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+    env.owner_call("set_require_lock_for_rewards", manifest_args!(token, false))
+        .expect_commit_success();
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    assert_eq!(env.claim(&locked), dec!(150));
+    assert_eq!(env.claim(&unlocked), dec!(50));
+}
+
+#[test]
+fn test_locked_rewards_wait_for_a_lock() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("set_require_lock_for_rewards", manifest_args!(token, true))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+
+    assert_eq!(env.claim(&id), dec!(0));
+}
+
+#[test]
+fn test_force_unlock_disabled_by_default() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+    env.call_with_id(&id, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    let receipt = env.call_with_bucket_and_id(reward, dec!(100), &id, "force_unlock", |bucket, proof| {
+        (token, proof, dec!(100), bucket)
+    });
+    expect_failure(&receipt, "Force unlocking is disabled.");
+}
+
+#[test]
+fn test_force_unlock_refunds_lock_payment_and_charges_penalty() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    env.owner_call("set_force_unlock_penalty", manifest_args!(Some(dec!("0.1"))))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+    env.call_with_id(&id, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    let before = env.balance(reward);
+    env.call_with_bucket_and_id(reward, dec!(60), &id, "force_unlock", |bucket, proof| {
+        (token, proof, dec!(50), bucket)
+    })
+    .expect_commit_success();
+    assert_eq!(env.balance(reward), before - dec!(50));
+    assert_eq!(env.staked(&id, token), dec!(95));
+
+    let receipt = env.call_with_bucket_and_id(reward, dec!(60), &id, "force_unlock", |bucket, proof| {
+        (token, proof, dec!(51), bucket)
+    });
+    expect_failure(&receipt, "Amount must be positive and no more than the locked amount.");
+
+    env.call_with_bucket_and_id(reward, dec!(60), &id, "force_unlock", |bucket, proof| {
+        (token, proof, dec!(50), bucket)
+    })
+    .expect_commit_success();
+    assert_eq!(env.balance(reward), before - dec!(100));
+    assert_eq!(env.staked(&id, token), dec!(90));
+
+    let has_locks: bool = env.read("has_active_locks", manifest_args!(id.clone()));
+    assert!(!has_locks);
+}
+
+#[test]
+fn test_dao_lock_survives_longer_lock() {
+    let mut env = TestEnv::with(Setup {
+        dao_controlled: true,
+        ..Setup::default()
+    });
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    env.owner_call("set_force_unlock_penalty", manifest_args!(Some(dec!(0))))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    let now = env.runner.get_current_proposer_timestamp_ms() / 1000;
+    env.owner_call(
+        "set_lock",
+        manifest_args!(token, Instant::new(now + 10 * 86400), id.clone()),
+    )
+    .expect_commit_success();
+    env.stake_to(&id, dec!(50)).expect_commit_success();
+    env.call_with_id(&id, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    let receipt = env.call_with_bucket_and_id(reward, dec!(10), &id, "force_unlock", |bucket, proof| {
+        (token, proof, dec!(10), bucket)
+    });
+    expect_failure(&receipt, "Tokens locked by a DAO vote cannot be force unlocked.");
+
+    env.advance_days(11);
+    env.call_with_bucket_and_id(reward, dec!(10), &id, "force_unlock", |bucket, proof| {
+        (token, proof, dec!(10), bucket)
+    })
+    .expect_commit_success();
+}
+
+#[test]
+fn test_set_lock_never_shortens_a_lock() {
+    let mut env = TestEnv::with(Setup {
+        dao_controlled: true,
+        ..Setup::default()
+    });
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call("set_force_unlock_penalty", manifest_args!(Some(dec!(0))))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    let now = env.runner.get_current_proposer_timestamp_ms() / 1000;
+    env.owner_call(
+        "set_lock",
+        manifest_args!(token, Instant::new(now + 10 * 86400), id.clone()),
+    )
+    .expect_commit_success();
+    env.owner_call(
+        "set_lock",
+        manifest_args!(token, Instant::new(now + 86400), id.clone()),
+    )
+    .expect_commit_success();
+
+    env.advance_days(2);
+    let receipt = env.start_unstake(&id, dec!(10), false);
+    expect_failure(&receipt, "You cannot unstake tokens currently participating in a vote.");
+    let receipt = env.call_with_bucket_and_id(reward, dec!(10), &id, "force_unlock", |bucket, proof| {
+        (token, proof, dec!(10), bucket)
+    });
+    expect_failure(&receipt, "Tokens locked by a DAO vote cannot be force unlocked.");
+
+    env.advance_days(9);
+    env.start_unstake(&id, dec!(10), false).expect_commit_success();
+}
+
+#[test]
+fn test_tiered_unstake_delay_spares_small_withdrawals() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("set_tiered_unstake_delay", manifest_args!(token, Some((dec!(50), 14i64))))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.start_unstake(&id, dec!(40), false).expect_commit_success();
+    let first = env.last_unstake_receipt();
+    env.advance_days(7);
+    env.finish_unstake(&first).expect_commit_success();
+
+    env.start_unstake(&id, dec!(40), false).expect_commit_success();
+    let second = env.last_unstake_receipt();
+    env.advance_days(7);
+    env.finish_unstake(&second).expect_commit_success();
+}
+
+#[test]
+fn test_tiered_unstake_delay_counts_split_withdrawals() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("set_tiered_unstake_delay", manifest_args!(token, Some((dec!(50), 14i64))))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.start_unstake(&id, dec!(40), false).expect_commit_success();
+    let first = env.last_unstake_receipt();
+    env.start_unstake(&id, dec!(40), false).expect_commit_success();
+    let second = env.last_unstake_receipt();
+
+    env.advance_days(7);
+    env.finish_unstake(&first).expect_commit_success();
+    let receipt = env.finish_unstake(&second);
+    expect_failure(&receipt, "You cannot unstake tokens before the redemption time.");
+
+    env.advance_days(7);
+    env.finish_unstake(&second).expect_commit_success();
+}
+
+// Schedules an airdrop of a new token to the stakers of `token` during a period, returning the airdropped token.
+fn schedule_airdrop(env: &mut TestEnv, period: i64, amount: Decimal) -> ResourceAddress {
+    let account = env.account;
+    let token = env.token;
+    let airdropped = env.runner.create_fungible_resource(dec!(1000), 18, account);
+    env.owner_call_with_bucket(airdropped, amount, "schedule_airdrop", |bucket| (token, period, bucket))
+        .expect_commit_success();
+    airdropped
+}
+
+#[test]
+fn test_airdrop_paid_on_claim() {
+    let mut env = TestEnv::new();
+    let airdropped = schedule_airdrop(&mut env, 0, dec!(50));
+    let first = env.stake(dec!(100));
+    let second = env.stake(dec!(400));
+
+    env.advance_days(1);
+
+    let before = env.balance(airdropped);
+    env.claim(&first);
+    assert_eq!(env.balance(airdropped), before + dec!(10));
+    env.claim(&second);
+    assert_eq!(env.balance(airdropped), before + dec!(50));
+}
+
+#[test]
+fn test_airdrop_paid_on_auto_claiming_unstake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let airdropped = schedule_airdrop(&mut env, 0, dec!(50));
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+
+    let before = env.balance(airdropped);
+    env.call_with_id(&id, "start_unstake", |proof| (proof, token, dec!(100), false, true, false))
+        .expect_commit_success();
+    assert_eq!(env.balance(airdropped), before + dec!(50));
+}
+
+#[test]
+fn test_swept_airdrop_paid_on_next_claim() {
+    let mut env = TestEnv::new();
+    let airdropped = schedule_airdrop(&mut env, 0, dec!(50));
+    let id = env.stake(dec!(100));
+    env.call_with_id(&id, "set_sweepable", |proof| (proof, true))
+        .expect_commit_success();
+
+    env.advance_days(1);
+
+    let before = env.balance(airdropped);
+    env.owner_call("sweep_claim", manifest_args!(id.clone()))
+        .expect_commit_success();
+    assert_eq!(env.balance(airdropped), before);
+
+    env.advance_days(1);
+    env.claim(&id);
+    assert_eq!(env.balance(airdropped), before + dec!(50));
+}
+
+#[test]
+fn test_airdrop_without_stakers_recoverable() {
+    let mut env = TestEnv::new();
+    let airdropped = schedule_airdrop(&mut env, 0, dec!(50));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let before = env.balance(airdropped);
+    env.owner_call("remove_airdrop_tokens", manifest_args!(airdropped, dec!(50)))
+        .expect_commit_success();
+    assert_eq!(env.balance(airdropped), before + dec!(50));
+}
+
+#[test]
+fn test_allocated_airdrop_tokens_cannot_be_removed() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let airdropped = schedule_airdrop(&mut env, 0, dec!(50));
+    env.owner_call_with_bucket(airdropped, dec!(30), "schedule_airdrop", |bucket| (token, 1i64, bucket))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+    let receipt = env.owner_call("remove_airdrop_tokens", manifest_args!(airdropped, dec!(1)));
+    expect_failure(&receipt, "Cannot remove airdropped tokens allocated to stakers.");
+
+    let before = env.balance(airdropped);
+    env.claim(&id);
+    assert_eq!(env.balance(airdropped), before + dec!(50));
+    let receipt = env.owner_call("remove_airdrop_tokens", manifest_args!(airdropped, dec!(1)));
+    expect_failure(&receipt, "Cannot remove airdropped tokens allocated to stakers.");
+
+    env.start_unstake(&id, dec!(100), false).expect_commit_success();
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+    env.owner_call("remove_airdrop_tokens", manifest_args!(airdropped, dec!(30)))
+        .expect_commit_success();
+    assert_eq!(env.balance(airdropped), before + dec!(80));
+}
+
+#[test]
+fn test_rebalance_rewards_moves_reward_budget() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let account = env.account;
+    let other = env.runner.create_fungible_resource(dec!(1000), 18, account);
+    env.owner_call("add_stakable", manifest_args!(other, REWARD_AMOUNT, default_lock()))
+        .expect_commit_success();
+    env.owner_call("set_reward_budget", manifest_args!(token, Some(dec!(150))))
+        .expect_commit_success();
+    env.owner_call("set_reward_budget", manifest_args!(other, Some(dec!(0))))
+        .expect_commit_success();
+
+    env.owner_call("rebalance_rewards", manifest_args!(token, other, dec!(100)))
+        .expect_commit_success();
+    let token_budget: Option<Decimal> = env.read("reward_budget", manifest_args!(token));
+    let other_budget: Option<Decimal> = env.read("reward_budget", manifest_args!(other));
+    assert_eq!(token_budget, Some(dec!(50)));
+    assert_eq!(other_budget, Some(dec!(100)));
+
+    let token_id = env.stake(dec!(100));
+    let other_id = env.stake_new_id(other, dec!(100));
+
+    env.advance_days(1);
+
+    assert_eq!(env.claim(&token_id), dec!(50));
+    assert_eq!(env.claim(&other_id), dec!(100));
+    let token_budget: Option<Decimal> = env.read("reward_budget", manifest_args!(token));
+    assert_eq!(token_budget, Some(dec!(0)));
+}
+
+#[test]
+fn test_rebalance_rewards_limited_to_unspent_budget() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let account = env.account;
+    let other = env.runner.create_fungible_resource(dec!(1000), 18, account);
+    env.owner_call("add_stakable", manifest_args!(other, REWARD_AMOUNT, default_lock()))
+        .expect_commit_success();
+    env.owner_call("set_reward_budget", manifest_args!(token, Some(dec!(150))))
+        .expect_commit_success();
+    env.owner_call("set_reward_budget", manifest_args!(other, Some(dec!(0))))
+        .expect_commit_success();
+    let token_id = env.stake(dec!(100));
+    let other_id = env.stake_new_id(other, dec!(100));
+
+    env.advance_days(1);
+    env.update_period().expect_commit_success();
+
+    let receipt = env.owner_call("rebalance_rewards", manifest_args!(token, other, dec!(51)));
+    expect_failure(&receipt, "Amount exceeds the unspent reward budget of the stakable.");
+    env.owner_call("rebalance_rewards", manifest_args!(token, other, dec!(50)))
+        .expect_commit_success();
+
+    env.advance_days(1);
+    assert_eq!(env.claim(&token_id), dec!(100));
+    assert_eq!(env.claim(&other_id), dec!(50));
+}
+
+#[test]
+fn test_rebalance_rewards_requires_budgets() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let account = env.account;
+    let other = env.runner.create_fungible_resource(dec!(1000), 18, account);
+    env.owner_call("add_stakable", manifest_args!(other, REWARD_AMOUNT, default_lock()))
+        .expect_commit_success();
+    env.owner_call("set_reward_budget", manifest_args!(token, Some(dec!(50))))
+        .expect_commit_success();
+
+    let receipt = env.owner_call("rebalance_rewards", manifest_args!(token, other, dec!(10)));
+    expect_failure(&receipt, "Both stakables must have a reward budget.");
+
+    env.owner_call("set_reward_budget", manifest_args!(other, Some(dec!(0))))
+        .expect_commit_success();
+    let receipt = env.owner_call("rebalance_rewards", manifest_args!(token, other, dec!(51)));
+    expect_failure(&receipt, "Amount exceeds the unspent reward budget of the stakable.");
+    let receipt = env.owner_call("rebalance_rewards", manifest_args!(token, token, dec!(10)));
+    expect_failure(&receipt, "Cannot rebalance rewards to the same stakable.");
+    let receipt = env.call("rebalance_rewards", manifest_args!(other, token, dec!(10)));
+    receipt.expect_commit_failure();
+}
+
+#[test]
+fn test_claim_rolls_period_without_update_period() {
+    let mut env = TestEnv::new();
+    let id = env.stake(dec!(100));
+
+    env.advance_days(1);
+
+    let preview: Decimal = env.read("preview_rewards", manifest_args!(id.clone()));
+    assert_eq!(preview, dec!(0));
+    assert_eq!(env.claim(&id), REWARD_AMOUNT);
+    let next_period: i64 = env.read("id_next_period", manifest_args!(id.clone()));
+    assert_eq!(next_period, 2);
+}
+
+#[test]
+fn test_get_lock_config_returns_configured_lock() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+
+    let lock: Lock = env.read("get_lock_config", manifest_args!(token));
+    assert_eq!(lock.duration, 30);
+    assert_eq!(lock.payment, dec!(1));
+    assert_eq!(lock.multiplier, dec!(1));
+
+    env.owner_call("edit_stakable", manifest_args!(token, REWARD_AMOUNT, double_weight_lock()))
+        .expect_commit_success();
+    let lock: Lock = env.read("get_lock_config", manifest_args!(token));
+    assert_eq!(lock.duration, 2);
+    assert_eq!(lock.payment, dec!(0));
+    assert_eq!(lock.multiplier, dec!(2));
+}
+
+#[test]
+fn test_preview_lock_reward_matches_lock_stake() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let reward = env.reward;
+    env.owner_call_with_bucket(reward, dec!(1000), "fill_lock_rewards", |bucket| (bucket,))
+        .expect_commit_success();
+    let id = env.stake(dec!(100));
+
+    let preview: Decimal = env.read("preview_lock_reward", manifest_args!(id.clone(), token));
+    assert_eq!(preview, dec!(100));
+    let before = env.balance(reward);
+    env.call_with_id(&id, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+    assert_eq!(env.balance(reward), before + preview);
+
+    let preview: Decimal = env.read("preview_lock_reward", manifest_args!(id, token));
+    assert_eq!(preview, dec!(0));
+}
+
+#[test]
+fn test_expired_locks_leave_total_locked() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    env.owner_call("edit_stakable", manifest_args!(token, REWARD_AMOUNT, double_weight_lock()))
+        .expect_commit_success();
+    let first = env.stake(dec!(100));
+    let second = env.stake(dec!(100));
+    env.call_with_id(&first, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+    env.call_with_id(&second, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+
+    env.call_with_id(&second, "start_unstake", |proof| (proof, token, dec!(40), false, false, true))
+        .expect_commit_success();
+    env.call("sync_locks", manifest_args!(second.clone()))
+        .expect_commit_success();
+    let total_locked: Decimal = env.read("total_locked", manifest_args!(token));
+    assert_eq!(total_locked, dec!(160));
+
+    for _ in 0..3 {
+        env.advance_days(1);
+        env.update_period().expect_commit_success();
+    }
+    let total_locked: Decimal = env.read("total_locked", manifest_args!(token));
+    assert_eq!(total_locked, dec!(0));
+
+    env.call("sync_locks", manifest_args!(first.clone()))
+        .expect_commit_success();
+    env.call("sync_locks", manifest_args!(second))
+        .expect_commit_success();
+    let total_locked: Decimal = env.read("total_locked", manifest_args!(token));
+    assert_eq!(total_locked, dec!(0));
+
+    env.update_id(&first).expect_commit_success();
+    env.call_with_id(&first, "lock_stake", |proof| (token, proof))
+        .expect_commit_success();
+    let total_locked: Decimal = env.read("total_locked", manifest_args!(token));
+    assert_eq!(total_locked, dec!(100));
+}
+
+#[test]
+fn test_lock_terms_match_added_stakable() {
+    let mut env = TestEnv::new();
+    let token = env.token;
+    let account = env.account;
+    let other = env.runner.create_fungible_resource(dec!(1000), 18, account);
+    env.owner_call("add_stakable", manifest_args!(other, REWARD_AMOUNT, double_weight_lock()))
+        .expect_commit_success();
 
-    Ok(())
+    let terms: (i64, Decimal) = env.read("lock_terms", manifest_args!(token));
+    assert_eq!(terms, (30, dec!(1)));
+    let terms: (i64, Decimal) = env.read("lock_terms", manifest_args!(other));
+    assert_eq!(terms, (2, dec!(0)));
 }
@@ -0,0 +1,43 @@
+use scrypto::prelude::*;
+
+// Minimal DEX for the staking tests, swapping any token for its output token at a fixed rate.
+#[blueprint]
+mod mock_dex {
+    struct MockDex {
+        // vaults holding the swapped tokens, per token
+        inputs: KeyValueStore<ResourceAddress, Vault>,
+        // vault holding the tokens paid out by swaps
+        output: Vault,
+        // amount of output tokens paid per swapped token
+        rate: Decimal,
+    }
+
+    impl MockDex {
+        pub fn instantiate(output: Bucket, rate: Decimal) -> Global<MockDex> {
+            Self {
+                inputs: KeyValueStore::new(),
+                output: Vault::with_bucket(output),
+                rate,
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .globalize()
+        }
+
+        pub fn swap(&mut self, input: Bucket, target: ResourceAddress) -> Bucket {
+            assert!(
+                target == self.output.resource_address(),
+                "Target token is not available."
+            );
+
+            let address: ResourceAddress = input.resource_address();
+            let amount: Decimal = input.amount() * self.rate;
+            if self.inputs.get(&address).is_none() {
+                self.inputs.insert(address, Vault::new(address));
+            }
+            self.inputs.get_mut(&address).unwrap().put(input);
+
+            self.output.take(amount)
+        }
+    }
+}